@@ -0,0 +1,219 @@
+package colour
+
+import (
+	"fmt"
+	"math"
+	"strconv"
+	"strings"
+)
+
+// Colour is the red, green, blue and white channels of a single LED.
+type Colour struct {
+	R, G, B, W byte
+}
+
+// ParseError is returned by Parse for a colour it can't read.
+type ParseError struct {
+	Colour  string
+	Problem string
+}
+
+func (e *ParseError) Error() string {
+	return fmt.Sprintf("colour %q %s", e.Colour, e.Problem)
+}
+
+// Parse reads a colour written as hex, with or without a leading #. Both the
+// full RRGGBB and RRGGBBWW forms and the short RGB and RGBW forms, where each
+// digit is doubled, are accepted.
+func Parse(s string) (Colour, error) {
+	digits := strings.TrimPrefix(s, "#")
+	switch len(digits) {
+	case 3, 4:
+		long := make([]byte, 0, 2*len(digits))
+		for i := 0; i < len(digits); i++ {
+			long = append(long, digits[i], digits[i])
+		}
+		digits = string(long)
+	case 6, 8:
+	default:
+		return Colour{}, &ParseError{Colour: s, Problem: "should have 3, 4, 6 or 8 hex digits"}
+	}
+
+	v, err := strconv.ParseUint(digits, 16, 32)
+	if err != nil {
+		return Colour{}, &ParseError{Colour: s, Problem: "isn't hex"}
+	}
+	if len(digits) == 6 {
+		v <<= 8
+	}
+	return Colour{R: byte(v >> 24), G: byte(v >> 16), B: byte(v >> 8), W: byte(v)}, nil
+}
+
+// String formats the colour as RRGGBBWW hex, the form Parse reads back.
+func (c Colour) String() string {
+	return fmt.Sprintf("%02x%02x%02x%02x", c.R, c.G, c.B, c.W)
+}
+
+// Scale multiplies every channel by f, saturating at full brightness.
+func (c Colour) Scale(f float64) Colour {
+	return Colour{R: scale(c.R, f), G: scale(c.G, f), B: scale(c.B, f), W: scale(c.W, f)}
+}
+
+// Add adds o to c channel by channel, stopping at full brightness.
+func (c Colour) Add(o Colour) Colour {
+	return Colour{R: add(c.R, o.R), G: add(c.G, o.G), B: add(c.B, o.B), W: add(c.W, o.W)}
+}
+
+// Sub takes o from c channel by channel, stopping at off.
+func (c Colour) Sub(o Colour) Colour {
+	return Colour{R: sub(c.R, o.R), G: sub(c.G, o.G), B: sub(c.B, o.B), W: sub(c.W, o.W)}
+}
+
+// Mix blends t of the way from c to o, channel by channel.
+func (c Colour) Mix(o Colour, t float64) Colour {
+	return Colour{R: mix(c.R, o.R, t), G: mix(c.G, o.G, t), B: mix(c.B, o.B, t), W: mix(c.W, o.W, t)}
+}
+
+// RGB returns the red, green and blue channels in that order, dropping white.
+func (c Colour) RGB() [3]byte {
+	return [3]byte{c.R, c.G, c.B}
+}
+
+// GRB returns the green, red and blue channels in that order, dropping white.
+func (c Colour) GRB() [3]byte {
+	return [3]byte{c.G, c.R, c.B}
+}
+
+// RGBW returns all four channels, red first.
+func (c Colour) RGBW() [4]byte {
+	return [4]byte{c.R, c.G, c.B, c.W}
+}
+
+// GRBW returns all four channels, green first.
+func (c Colour) GRBW() [4]byte {
+	return [4]byte{c.G, c.R, c.B, c.W}
+}
+
+// Colour temperatures FromTemperature accepts, in kelvin.
+const (
+	MinTemperature = 1000
+	MaxTemperature = 12000
+)
+
+// FromTemperature approximates the colour of a black body at kelvin, after
+// Tanner Helland's curve fit, clamped to MinTemperature to MaxTemperature.
+// The white the red, green and blue have in common goes to the W channel.
+func FromTemperature(kelvin int) Colour {
+	if kelvin < MinTemperature {
+		kelvin = MinTemperature
+	} else if kelvin > MaxTemperature {
+		kelvin = MaxTemperature
+	}
+	t := float64(kelvin) / 100
+
+	r, g, b := 255.0, 255.0, 255.0
+	if t <= 66 {
+		g = 99.4708025861*math.Log(t) - 161.1195681661
+	} else {
+		r = 329.698727446 * math.Pow(t-60, -0.1332047592)
+		g = 288.1221695283 * math.Pow(t-60, -0.0755148492)
+	}
+	if t <= 19 {
+		b = 0
+	} else if t < 66 {
+		b = 138.5177312231*math.Log(t-10) - 305.0447927307
+	}
+
+	c := Colour{R: clamp(r), G: clamp(g), B: clamp(b)}
+	c.W = c.R
+	if c.G < c.W {
+		c.W = c.G
+	}
+	if c.B < c.W {
+		c.W = c.B
+	}
+	c.R, c.G, c.B = c.R-c.W, c.G-c.W, c.B-c.W
+	return c
+}
+
+func clamp(v float64) byte {
+	return byte(math.Round(math.Max(0, math.Min(0xff, v))))
+}
+
+// Luminance is the WCAG relative luminance of the colour from 0 for black to
+// 1 for white, taking white as lighting red, green and blue alike.
+func (c Colour) Luminance() float64 {
+	r, g, b := linear(add(c.R, c.W)), linear(add(c.G, c.W)), linear(add(c.B, c.W))
+	return 0.2126*r + 0.7152*g + 0.0722*b
+}
+
+// ReadableText returns black or white, whichever text on the colour is
+// easier to read.
+func (c Colour) ReadableText() Colour {
+	// Where black and white text have the same WCAG contrast ratio.
+	if c.Luminance() > 0.179 {
+		return Colour{}
+	}
+	return Colour{R: 0xff, G: 0xff, B: 0xff}
+}
+
+func linear(v byte) float64 {
+	f := float64(v) / 0xff
+	if f <= 0.04045 {
+		return f / 12.92
+	}
+	return math.Pow((f+0.055)/1.055, 2.4)
+}
+
+// BlendMode is how Blend combines two colours.
+type BlendMode int
+
+const (
+	// Multiply darkens, tinting c towards o; white leaves c unchanged.
+	Multiply BlendMode = iota
+	// Screen lightens, the inverse of multiplying the inverses; black leaves
+	// c unchanged.
+	Screen
+)
+
+// Blend combines c with o channel by channel, as the base for overlays.
+func (c Colour) Blend(o Colour, mode BlendMode) Colour {
+	f := multiply
+	if mode == Screen {
+		f = screen
+	}
+	return Colour{R: f(c.R, o.R), G: f(c.G, o.G), B: f(c.B, o.B), W: f(c.W, o.W)}
+}
+
+func multiply(a, b byte) byte {
+	return byte((int(a)*int(b) + 127) / 255)
+}
+
+func screen(a, b byte) byte {
+	return 0xff - multiply(0xff-a, 0xff-b)
+}
+
+func add(a, b byte) byte {
+	if int(a)+int(b) > 0xff {
+		return 0xff
+	}
+	return a + b
+}
+
+func sub(a, b byte) byte {
+	if b > a {
+		return 0
+	}
+	return a - b
+}
+
+func scale(v byte, f float64) byte {
+	if s := float64(v) * f; s < 0xff {
+		return byte(s)
+	}
+	return 0xff
+}
+
+func mix(a, b byte, t float64) byte {
+	return byte(math.Round(float64(a) + (float64(b)-float64(a))*t))
+}
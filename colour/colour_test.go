@@ -0,0 +1,149 @@
+package colour
+
+import (
+	"errors"
+	"math"
+	"testing"
+)
+
+func TestParse(t *testing.T) {
+	tests := []struct {
+		in   string
+		want Colour
+		err  bool
+	}{
+		{in: "ff0000", want: Colour{R: 0xff}},
+		{in: "#ff0000", want: Colour{R: 0xff}},
+		{in: "00ff0080", want: Colour{G: 0xff, W: 0x80}},
+		{in: "#00FF0080", want: Colour{G: 0xff, W: 0x80}},
+		{in: "f00", want: Colour{R: 0xff}},
+		{in: "#f00", want: Colour{R: 0xff}},
+		{in: "#f00a", want: Colour{R: 0xff, W: 0xaa}},
+		{in: "123", want: Colour{R: 0x11, G: 0x22, B: 0x33}},
+		{in: "", err: true},
+		{in: "#", err: true},
+		{in: "ff00", want: Colour{R: 0xff, G: 0xff}},
+		{in: "ff000", err: true},
+		{in: "ff00000", err: true},
+		{in: "ff0000000", err: true},
+		{in: "gg0000", err: true},
+		{in: "##f00", err: true},
+	}
+	for _, tt := range tests {
+		got, err := Parse(tt.in)
+		if tt.err {
+			var parseErr *ParseError
+			if !errors.As(err, &parseErr) {
+				t.Errorf("Parse(%q) = %v, %v, want a ParseError", tt.in, got, err)
+			}
+			continue
+		}
+		if err != nil || got != tt.want {
+			t.Errorf("Parse(%q) = %v, %v, want %v", tt.in, got, err, tt.want)
+		}
+	}
+}
+
+func TestBlend(t *testing.T) {
+	c := Colour{R: 200, G: 100, B: 0, W: 0xff}
+	o := Colour{R: 0xff, G: 128, B: 0xff, W: 0}
+	tests := []struct {
+		name string
+		mode BlendMode
+		o    Colour
+		want Colour
+	}{
+		{name: "multiply", mode: Multiply, o: o, want: Colour{R: 200, G: 50, B: 0, W: 0}},
+		{name: "screen", mode: Screen, o: o, want: Colour{R: 0xff, G: 178, B: 0xff, W: 0xff}},
+		{name: "multiply by white", mode: Multiply, o: Colour{R: 0xff, G: 0xff, B: 0xff, W: 0xff}, want: c},
+		{name: "screen with black", mode: Screen, o: Colour{}, want: c},
+	}
+	for _, tt := range tests {
+		if got := c.Blend(tt.o, tt.mode); got != tt.want {
+			t.Errorf("%s: %v.Blend(%v) = %v, want %v", tt.name, c, tt.o, got, tt.want)
+		}
+	}
+}
+
+func TestFromTemperature(t *testing.T) {
+	warm := FromTemperature(2700)
+	if warm.R <= warm.B {
+		t.Errorf("2700K = %v, want more red than blue", warm)
+	}
+	daylight := FromTemperature(6500)
+	if daylight.W < 0xf0 || daylight.R > 10 || daylight.G > 10 || daylight.B > 10 {
+		t.Errorf("6500K = %v, want nearly all of it white", daylight)
+	}
+	clamps := []struct{ kelvin, to int }{
+		{500, MinTemperature},
+		{0, MinTemperature},
+		{20000, MaxTemperature},
+	}
+	for _, tt := range clamps {
+		if got, want := FromTemperature(tt.kelvin), FromTemperature(tt.to); got != want {
+			t.Errorf("FromTemperature(%d) = %v, want %v as for %dK", tt.kelvin, got, want, tt.to)
+		}
+	}
+}
+
+func TestChannelOrders(t *testing.T) {
+	c := Colour{R: 10, G: 20, B: 30, W: 40}
+	if got, want := c.RGB(), [3]byte{10, 20, 30}; got != want {
+		t.Errorf("RGB() = %v, want %v", got, want)
+	}
+	if got, want := c.GRB(), [3]byte{20, 10, 30}; got != want {
+		t.Errorf("GRB() = %v, want %v", got, want)
+	}
+	if got, want := c.RGBW(), [4]byte{10, 20, 30, 40}; got != want {
+		t.Errorf("RGBW() = %v, want %v", got, want)
+	}
+	if got, want := c.GRBW(), [4]byte{20, 10, 30, 40}; got != want {
+		t.Errorf("GRBW() = %v, want %v", got, want)
+	}
+}
+
+func TestArithmetic(t *testing.T) {
+	a := Colour{R: 200, G: 100, B: 0, W: 3}
+	b := Colour{R: 100, G: 50, B: 10, W: 0xff}
+	tests := []struct {
+		name string
+		got  Colour
+		want Colour
+	}{
+		{name: "add saturates", got: a.Add(b), want: Colour{R: 0xff, G: 150, B: 10, W: 0xff}},
+		{name: "sub stops at off", got: a.Sub(b), want: Colour{R: 100, G: 50, B: 0, W: 0}},
+		{name: "halved", got: a.Scale(0.5), want: Colour{R: 100, G: 50, B: 0, W: 1}},
+		{name: "scaled up saturates", got: a.Scale(1.5), want: Colour{R: 0xff, G: 150, B: 0, W: 4}},
+		{name: "scaled to off", got: a.Scale(0), want: Colour{}},
+	}
+	for _, tt := range tests {
+		if tt.got != tt.want {
+			t.Errorf("%s: got %v, want %v", tt.name, tt.got, tt.want)
+		}
+	}
+}
+
+func TestLuminance(t *testing.T) {
+	black, white := Colour{}, Colour{R: 0xff, G: 0xff, B: 0xff}
+	tests := []struct {
+		name string
+		c    Colour
+		want float64
+		text Colour
+	}{
+		{name: "black", c: black, want: 0, text: white},
+		{name: "white", c: white, want: 1, text: black},
+		{name: "white LED", c: Colour{W: 0xff}, want: 1, text: black},
+		{name: "red", c: Colour{R: 0xff}, want: 0.2126, text: black},
+		{name: "blue", c: Colour{B: 0xff}, want: 0.0722, text: white},
+		{name: "yellow", c: Colour{R: 0xff, G: 0xff}, want: 0.9278, text: black},
+	}
+	for _, tt := range tests {
+		if got := tt.c.Luminance(); math.Abs(got-tt.want) > 1e-9 {
+			t.Errorf("%s: Luminance() = %g, want %g", tt.name, got, tt.want)
+		}
+		if got := tt.c.ReadableText(); got != tt.text {
+			t.Errorf("%s: ReadableText() = %v, want %v", tt.name, got, tt.text)
+		}
+	}
+}
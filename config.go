@@ -0,0 +1,425 @@
+package main
+
+import (
+	"flag"
+	"fmt"
+	"io"
+	"math"
+	"os"
+	"path/filepath"
+	"reflect"
+	"sort"
+	"strings"
+	"time"
+
+	"github.com/shift/systemd-status-leds/colour"
+	"github.com/shift/systemd-status-leds/led"
+	"github.com/shift/systemd-status-leds/strip"
+
+	"github.com/mitchellh/mapstructure"
+	"github.com/spf13/viper"
+	"go.uber.org/zap"
+	"gopkg.in/yaml.v3"
+	"periph.io/x/conn/v3/physic"
+)
+
+type Service struct {
+	Unit        string            `mapstructure:"name" yaml:"name"`
+	States      map[string]string `mapstructure:"states_map" yaml:"states_map,omitempty"`
+	Watch       bool              `mapstructure:"watch" yaml:"watch,omitempty"`
+	Label       string            `mapstructure:"label" yaml:"label,omitempty"`
+	Inherit     *bool             `mapstructure:"inherit" yaml:"inherit,omitempty"` // unset is true
+	ColoursFrom string            `mapstructure:"colours_from" yaml:"colours_from,omitempty"`
+}
+
+// Inherits reports whether the service falls back on the strip wide colours
+// for states its states_map leaves out.
+func (s Service) Inherits() bool {
+	return s.Inherit == nil || *s.Inherit
+}
+
+type Config struct {
+	Services []Service     `mapstructure:"services"`
+	Debounce time.Duration `mapstructure:"debounce"`
+	Strip    strip.Config
+	Status   struct {
+		Socket  string
+		WebAddr string `mapstructure:"web_addr" yaml:"web_addr"`
+	}
+	Events struct {
+		Buffer   int
+		Interval time.Duration // how often units are polled, changes in between coalesce
+	}
+	DBus struct {
+		Address string
+		Retry   time.Duration // keep trying to connect for this long
+	}
+	Watchdog struct {
+		Timeout time.Duration
+		Colour  string
+	}
+	MissingUnits string            `mapstructure:"missing_units" yaml:"missing_units"` // skip, warn or error
+	MonitorMode  string            `mapstructure:"monitor_mode" yaml:"monitor_mode"`   // signal, poll or auto
+	Palette      map[string]string `mapstructure:"palette" yaml:"palette,omitempty"`
+	PaletteFile  string            `mapstructure:"palette_file" yaml:"palette_file,omitempty"`
+}
+
+// DefaultConfig is a starting point for a new config, with a single service
+// and the standard colour for every state.
+func DefaultConfig() Config {
+	var c Config
+	c.Services = []Service{{Unit: "multi-user.target"}}
+	c.Strip = strip.Config{
+		Length:     5,
+		Channels:   4,
+		Hertz:      10,
+		SpiSpeedHz: int(strip.DefaultSpiSpeed / physic.Hertz),
+		SpiMode:    strip.DefaultSpiMode,
+		SpiBits:    strip.DefaultSpiBits,
+		Spidev:     "0.0",
+		Colours:    make(map[string]string),
+	}
+	c.Strip.Loading.Colour = strip.DefaultLoadingColour
+	c.Strip.Loading.Style = "solid"
+	c.MissingUnits = "warn"
+	c.MonitorMode = "poll"
+	c.Events.Interval = defaultEventInterval
+	c.DBus.Retry = defaultConnectRetry
+	for state, colour := range led.DefaultColours {
+		c.Strip.Colours[state] = colour
+	}
+	return c
+}
+
+// Configuration loads the config file named by -config, ./config by default,
+// into C, panicking if it can't be read or is wrong.
+func Configuration() {
+	c, err := loadConfig(*configFile)
+	if err != nil {
+		logr.Panic("config file", zap.Error(err))
+	}
+	C = c
+}
+
+// loadConfig reads, resolves and validates the config file at path, or at
+// ./config if path is empty. The file is JSON if it ends in .json and YAML
+// otherwise.
+func loadConfig(path string) (Config, error) {
+	var c Config
+	v := viper.New()
+	v.SetConfigName("config")
+	v.SetConfigType("yaml")
+	v.AddConfigPath(".")
+	if path != "" {
+		v.SetConfigFile(path)
+		if strings.EqualFold(filepath.Ext(path), ".json") {
+			v.SetConfigType("json")
+		}
+	}
+	v.SetDefault("strip.spi_speed_hz", int64(strip.DefaultSpiSpeed/physic.Hertz))
+	v.SetDefault("strip.spi_mode", strip.DefaultSpiMode)
+	v.SetDefault("strip.spi_bits", strip.DefaultSpiBits)
+	v.SetDefault("strip.loading.colour", strip.DefaultLoadingColour)
+	v.SetDefault("strip.loading.style", "solid")
+	v.SetDefault("missing_units", "warn")
+	v.SetDefault("monitor_mode", "poll")
+	v.SetDefault("events.interval", defaultEventInterval)
+	v.SetDefault("dbus.retry", defaultConnectRetry)
+	if err := v.ReadInConfig(); err != nil {
+		return c, err
+	}
+	err := v.Unmarshal(&c, viper.DecodeHook(mapstructure.ComposeDecodeHookFunc(
+		mapstructure.StringToTimeDurationHookFunc(),
+		mapstructure.StringToSliceHookFunc(","),
+		colourListHook,
+	)))
+	if err != nil {
+		return c, err
+	}
+	// Older configs use animations_enabled and clear_on_exit, which are true
+	// unless set otherwise.
+	if v.IsSet("strip.animations_enabled") {
+		c.Strip.DisableAnimations = !v.GetBool("strip.animations_enabled")
+	}
+	if v.IsSet("strip.clear_on_exit") {
+		c.Strip.KeepOnExit = !v.GetBool("strip.clear_on_exit")
+	}
+	if err := c.ResolveColoursFrom(); err != nil {
+		return c, err
+	}
+	if err := c.ResolvePalette(); err != nil {
+		return c, err
+	}
+	return c, c.Validate()
+}
+
+// colourListHook lets a colour be written as a [r, g, b] or [r, g, b, w] list
+// of values from 0 to 255 as well as in hex, turning the list into hex.
+func colourListHook(from reflect.Type, to reflect.Type, data interface{}) (interface{}, error) {
+	if from.Kind() != reflect.Slice || to.Kind() != reflect.String {
+		return data, nil
+	}
+	values := reflect.ValueOf(data)
+	if values.Len() != 3 && values.Len() != 4 {
+		return nil, fmt.Errorf("colour %v needs 3 or 4 channels, not %d", data, values.Len())
+	}
+	var c colour.Colour
+	channels := []*byte{&c.R, &c.G, &c.B, &c.W}
+	for i := 0; i < values.Len(); i++ {
+		v, err := channelValue(values.Index(i).Interface())
+		if err != nil {
+			return nil, fmt.Errorf("colour %v: %w", data, err)
+		}
+		*channels[i] = v
+	}
+	return c.String(), nil
+}
+
+// channelValue reads one channel of a colour list, as an int from YAML or a
+// float64 from JSON.
+func channelValue(v interface{}) (byte, error) {
+	var f float64
+	switch n := v.(type) {
+	case int:
+		f = float64(n)
+	case int64:
+		f = float64(n)
+	case uint64:
+		f = float64(n)
+	case float64:
+		f = n
+	default:
+		return 0, fmt.Errorf("channel %v isn't a number", v)
+	}
+	if f != math.Trunc(f) || f < 0 || f > 0xff {
+		return 0, fmt.Errorf("channel %v should be a whole number from 0 to 255", v)
+	}
+	return byte(f), nil
+}
+
+// ResolveColoursFrom fills in the states_map of every service with
+// colours_from set from the service it names, following chains of them. The
+// service's own entries win. A name that isn't a service, or a chain leading
+// back on itself, is an error.
+func (c *Config) ResolveColoursFrom() error {
+	byName := make(map[string]int, len(c.Services))
+	for i, service := range c.Services {
+		byName[service.Unit] = i
+	}
+	resolved := make([]map[string]string, len(c.Services))
+	var resolve func(i int, visiting map[int]bool) (map[string]string, error)
+	resolve = func(i int, visiting map[int]bool) (map[string]string, error) {
+		if resolved[i] != nil {
+			return resolved[i], nil
+		}
+		service := c.Services[i]
+		states := make(map[string]string)
+		if service.ColoursFrom != "" {
+			from, ok := byName[service.ColoursFrom]
+			if !ok {
+				return nil, fmt.Errorf("service %s colours_from: no service %s", service.Unit, service.ColoursFrom)
+			}
+			if visiting[from] {
+				return nil, fmt.Errorf("service %s colours_from: %s leads back to itself", service.Unit, service.ColoursFrom)
+			}
+			visiting[i] = true
+			inherited, err := resolve(from, visiting)
+			if err != nil {
+				return nil, err
+			}
+			for state, code := range inherited {
+				states[state] = code
+			}
+		}
+		for state, code := range service.States {
+			states[state] = code
+		}
+		resolved[i] = states
+		return states, nil
+	}
+	for i := range c.Services {
+		if _, err := resolve(i, make(map[int]bool)); err != nil {
+			return err
+		}
+	}
+	for i := range c.Services {
+		if c.Services[i].ColoursFrom != "" {
+			c.Services[i].States = resolved[i]
+		}
+	}
+	return nil
+}
+
+// palettePrefix marks a colour naming a palette entry rather than giving hex.
+const palettePrefix = "palette:"
+
+// ResolvePalette replaces every colour written as palette:name with the
+// palette's colour of that name. Entries in palette_file are read first, so
+// the inline palette can override them. A name in neither is an error.
+func (c *Config) ResolvePalette() error {
+	palette := make(map[string]string)
+	if c.PaletteFile != "" {
+		data, err := os.ReadFile(c.PaletteFile)
+		if err != nil {
+			return fmt.Errorf("palette_file: %w", err)
+		}
+		var entries map[string]string
+		if err := yaml.Unmarshal(data, &entries); err != nil {
+			return fmt.Errorf("palette_file %s: %w", c.PaletteFile, err)
+		}
+		for name, code := range entries {
+			palette[strings.ToLower(name)] = code // as viper does the inline names
+		}
+	}
+	for name, code := range c.Palette {
+		palette[name] = code
+	}
+
+	lookup := func(code string) (string, error) {
+		if !strings.HasPrefix(code, palettePrefix) {
+			return code, nil
+		}
+		name := strings.TrimPrefix(code, palettePrefix)
+		if found, ok := palette[strings.ToLower(name)]; ok {
+			return found, nil
+		}
+		return "", fmt.Errorf("no palette entry %q", name)
+	}
+	var err error
+	for state, code := range c.Strip.Colours {
+		if c.Strip.Colours[state], err = lookup(code); err != nil {
+			return fmt.Errorf("colours %s: %w", state, err)
+		}
+	}
+	for _, service := range c.Services {
+		for state, code := range service.States {
+			if service.States[state], err = lookup(code); err != nil {
+				return fmt.Errorf("service %s states_map %s: %w", service.Unit, state, err)
+			}
+		}
+	}
+	if c.Strip.Loading.Colour, err = lookup(c.Strip.Loading.Colour); err != nil {
+		return fmt.Errorf("loading colour: %w", err)
+	}
+	if c.Watchdog.Colour, err = lookup(c.Watchdog.Colour); err != nil {
+		return fmt.Errorf("watchdog colour: %w", err)
+	}
+	if c.Strip.Heartbeat.Colour, err = lookup(c.Strip.Heartbeat.Colour); err != nil {
+		return fmt.Errorf("heartbeat colour: %w", err)
+	}
+	return nil
+}
+
+// Validate checks the strip section and that every service lands on a pixel
+// of the strip once start_offset and reverse are taken into account.
+func (c *Config) Validate() error {
+	if err := c.Strip.Validate(); err != nil {
+		return err
+	}
+	if c.Events.Interval <= 0 {
+		return fmt.Errorf("events interval must be positive, not %s", c.Events.Interval)
+	}
+	switch c.MonitorMode {
+	case "signal", "poll", "auto":
+	default:
+		return fmt.Errorf("monitor_mode %q should be signal, poll or auto", c.MonitorMode)
+	}
+	switch c.MissingUnits {
+	case "skip", "warn", "error":
+	default:
+		return fmt.Errorf("missing_units %q should be skip, warn or error", c.MissingUnits)
+	}
+	if c.Watchdog.Colour != "" {
+		if _, err := colour.Parse(c.Watchdog.Colour); err != nil {
+			return fmt.Errorf("watchdog colour: %w", err)
+		}
+	}
+	if c.Strip.HeartbeatSlot() >= 0 && len(c.Services) >= c.Strip.Length-c.Strip.StartOffset {
+		return fmt.Errorf("%d services leave no pixel for the heartbeat", len(c.Services))
+	}
+	for i, service := range c.Services {
+		for state, code := range service.States {
+			if _, err := colour.Parse(code); err != nil {
+				return fmt.Errorf("service %s states_map %s: %w", service.Unit, state, err)
+			}
+		}
+		if p := c.Strip.Position(i); p < 0 || p >= c.Strip.Length {
+			return fmt.Errorf("service %s would be pixel %d, off a strip of %d after start_offset %d", service.Unit, p, c.Strip.Length, c.Strip.StartOffset)
+		}
+	}
+	return nil
+}
+
+// YAML renders the config in the same layout Configuration reads.
+func (c *Config) YAML() ([]byte, error) {
+	return yaml.Marshal(c)
+}
+
+// WriteFile writes the config to path as YAML.
+func (c *Config) WriteFile(path string) error {
+	buf, err := c.YAML()
+	if err != nil {
+		return err
+	}
+	return os.WriteFile(path, buf, 0644)
+}
+
+// genConfig writes DefaultConfig as YAML to the path in args, or to stdout
+// without one. An existing file is only replaced with -force.
+func genConfig(args []string) error {
+	flags := flag.NewFlagSet("gen-config", flag.ExitOnError)
+	force := flags.Bool("force", false, "overwrite an existing file")
+	_ = flags.Parse(args)
+
+	c := DefaultConfig()
+	if flags.NArg() == 0 {
+		buf, err := c.YAML()
+		if err != nil {
+			return err
+		}
+		_, err = os.Stdout.Write(buf)
+		return err
+	}
+
+	path := flags.Arg(0)
+	if _, err := os.Stat(path); err == nil && !*force {
+		return fmt.Errorf("%s already exists, use -force to overwrite it", path)
+	}
+	return c.WriteFile(path)
+}
+
+// printResolved writes the colour every service shows for each state, after
+// palette references, inheritance and the dark modes are worked out.
+func printResolved(w io.Writer) error {
+	states := make(map[string]bool)
+	for state := range led.DefaultColours {
+		states[state] = true
+	}
+	for state := range C.Strip.Colours {
+		states[state] = true
+	}
+	for _, service := range C.Services {
+		for state := range service.States {
+			states[state] = true
+		}
+	}
+	keys := make([]string, 0, len(states))
+	for state := range states {
+		keys = append(keys, state)
+	}
+	sort.Strings(keys)
+
+	for _, service := range C.Services {
+		if _, err := fmt.Fprintln(w, service.Unit); err != nil {
+			return err
+		}
+		for _, key := range keys {
+			state, sub, _ := strings.Cut(key, "/")
+			look, _ := resolve(service, state, sub)
+			if _, err := fmt.Fprintf(w, "  %-20s %s\n", key, look.Colour); err != nil {
+				return err
+			}
+		}
+	}
+	return nil
+}
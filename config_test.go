@@ -0,0 +1,374 @@
+package main
+
+import (
+	"fmt"
+	"os"
+	"path/filepath"
+	"reflect"
+	"strings"
+	"testing"
+	"time"
+)
+
+func TestResolveColoursFrom(t *testing.T) {
+	tests := []struct {
+		name     string
+		services []Service
+		want     []map[string]string
+		err      string
+	}{
+		{
+			name: "own entries win",
+			services: []Service{
+				{Unit: "a", States: map[string]string{"active": "00ff00", "failed": "ff0000"}},
+				{Unit: "b", States: map[string]string{"failed": "ff8000"}, ColoursFrom: "a"},
+			},
+			want: []map[string]string{
+				{"active": "00ff00", "failed": "ff0000"},
+				{"active": "00ff00", "failed": "ff8000"},
+			},
+		},
+		{
+			name: "chain",
+			services: []Service{
+				{Unit: "c", ColoursFrom: "b"},
+				{Unit: "b", ColoursFrom: "a"},
+				{Unit: "a", States: map[string]string{"active": "0000ff"}},
+			},
+			want: []map[string]string{
+				{"active": "0000ff"},
+				{"active": "0000ff"},
+				{"active": "0000ff"},
+			},
+		},
+		{
+			name:     "unknown service",
+			services: []Service{{Unit: "a", ColoursFrom: "nope"}},
+			err:      "no service nope",
+		},
+		{
+			name:     "itself",
+			services: []Service{{Unit: "a", ColoursFrom: "a"}},
+			err:      "leads back to itself",
+		},
+		{
+			name: "cycle",
+			services: []Service{
+				{Unit: "a", ColoursFrom: "b"},
+				{Unit: "b", ColoursFrom: "c"},
+				{Unit: "c", ColoursFrom: "a"},
+			},
+			err: "leads back to itself",
+		},
+		{
+			name: "cycle off to the side",
+			services: []Service{
+				{Unit: "a", States: map[string]string{"active": "ffffff"}},
+				{Unit: "b", ColoursFrom: "c"},
+				{Unit: "c", ColoursFrom: "b"},
+			},
+			err: "leads back to itself",
+		},
+	}
+	for _, tt := range tests {
+		t.Run(tt.name, func(t *testing.T) {
+			c := Config{Services: tt.services}
+			err := c.ResolveColoursFrom()
+			if tt.err != "" {
+				if err == nil || !strings.Contains(err.Error(), tt.err) {
+					t.Fatalf("ResolveColoursFrom() = %v, want an error containing %q", err, tt.err)
+				}
+				return
+			}
+			if err != nil {
+				t.Fatalf("ResolveColoursFrom() = %v", err)
+			}
+			for i, service := range c.Services {
+				if !reflect.DeepEqual(service.States, tt.want[i]) {
+					t.Errorf("service %s states = %v, want %v", service.Unit, service.States, tt.want[i])
+				}
+			}
+		})
+	}
+}
+
+func TestColourListHook(t *testing.T) {
+	stringType := reflect.TypeOf("")
+	tests := []struct {
+		name string
+		data interface{}
+		to   reflect.Type
+		want interface{}
+		err  bool
+	}{
+		{name: "yaml rgb", data: []interface{}{255, 0, 0}, to: stringType, want: "ff000000"},
+		{name: "yaml rgbw", data: []interface{}{0, 16, 32, 255}, to: stringType, want: "001020ff"},
+		{name: "json", data: []interface{}{float64(1), float64(2), float64(3)}, to: stringType, want: "01020300"},
+		{name: "int64", data: []interface{}{int64(10), uint64(11), 12}, to: stringType, want: "0a0b0c00"},
+		{name: "hex left alone", data: "ff0000", to: stringType, want: "ff0000"},
+		{name: "not for a string", data: []interface{}{"a", "b"}, to: reflect.TypeOf([]string{}), want: []interface{}{"a", "b"}},
+		{name: "too few", data: []interface{}{1, 2}, to: stringType, err: true},
+		{name: "too many", data: []interface{}{1, 2, 3, 4, 5}, to: stringType, err: true},
+		{name: "over 255", data: []interface{}{256, 0, 0}, to: stringType, err: true},
+		{name: "negative", data: []interface{}{-1, 0, 0}, to: stringType, err: true},
+		{name: "fraction", data: []interface{}{0.5, 0, 0}, to: stringType, err: true},
+		{name: "not a number", data: []interface{}{"ff", 0, 0}, to: stringType, err: true},
+	}
+	for _, tt := range tests {
+		got, err := colourListHook(reflect.TypeOf(tt.data), tt.to, tt.data)
+		if tt.err {
+			if err == nil {
+				t.Errorf("%s: colourListHook(%v) = %v, want an error", tt.name, tt.data, got)
+			}
+			continue
+		}
+		if err != nil || !reflect.DeepEqual(got, tt.want) {
+			t.Errorf("%s: colourListHook(%v) = %v, %v, want %v", tt.name, tt.data, got, err, tt.want)
+		}
+	}
+}
+
+func TestLoadConfigJSONMatchesYAML(t *testing.T) {
+	files := map[string]string{
+		"config.yaml": `
+monitor_mode: auto
+debounce: 200ms
+palette:
+  warm: ff8000
+strip:
+  length: 8
+  channels: 4
+  hertz: 30
+  colours:
+    active: [0, 255, 0]
+    failed: palette:warm
+  min_hold:
+    failed: 2s
+services:
+  - name: a.service
+    label: A
+  - name: b.service
+    states_map:
+      active: 0000ff
+`,
+		"config.json": `{
+  "monitor_mode": "auto",
+  "debounce": "200ms",
+  "palette": {"warm": "ff8000"},
+  "strip": {
+    "length": 8,
+    "channels": 4,
+    "hertz": 30,
+    "colours": {"active": [0, 255, 0], "failed": "palette:warm"},
+    "min_hold": {"failed": "2s"}
+  },
+  "services": [
+    {"name": "a.service", "label": "A"},
+    {"name": "b.service", "states_map": {"active": "0000ff"}}
+  ]
+}`,
+	}
+	dir := t.TempDir()
+	configs := make(map[string]Config)
+	for name, content := range files {
+		path := filepath.Join(dir, name)
+		if err := os.WriteFile(path, []byte(content), 0o644); err != nil {
+			t.Fatal(err)
+		}
+		c, err := loadConfig(path)
+		if err != nil {
+			t.Fatalf("loadConfig(%s): %v", name, err)
+		}
+		configs[name] = c
+	}
+	yamlConfig, jsonConfig := configs["config.yaml"], configs["config.json"]
+	if !reflect.DeepEqual(yamlConfig, jsonConfig) {
+		t.Errorf("JSON config = %+v, want the same as YAML %+v", jsonConfig, yamlConfig)
+	}
+	if got := yamlConfig.Strip.Colours["failed"]; got != "ff8000" {
+		t.Errorf("failed colour = %q, want the palette's ff8000", got)
+	}
+	if got := yamlConfig.Strip.MinHold["failed"]; got != 2*time.Second {
+		t.Errorf("failed min_hold = %s, want 2s", got)
+	}
+}
+
+func TestDefaultConfigRoundTrip(t *testing.T) {
+	c := DefaultConfig()
+	path := filepath.Join(t.TempDir(), "config.yaml")
+	if err := c.WriteFile(path); err != nil {
+		t.Fatalf("WriteFile: %v", err)
+	}
+	loaded, err := loadConfig(path)
+	if err != nil {
+		t.Fatalf("loadConfig: %v", err)
+	}
+	// Compared as YAML, since an empty map may come back as none at all.
+	want, err := c.YAML()
+	if err != nil {
+		t.Fatalf("YAML: %v", err)
+	}
+	got, err := loaded.YAML()
+	if err != nil {
+		t.Fatalf("YAML: %v", err)
+	}
+	if string(got) != string(want) {
+		t.Errorf("after a round trip the config is\n%s\nwant\n%s", got, want)
+	}
+}
+
+func TestGenConfig(t *testing.T) {
+	path := filepath.Join(t.TempDir(), "config.yaml")
+	if err := genConfig([]string{path}); err != nil {
+		t.Fatalf("gen-config: %v", err)
+	}
+	if _, err := loadConfig(path); err != nil {
+		t.Errorf("generated config doesn't load: %v", err)
+	}
+	if err := os.WriteFile(path, []byte("edited"), 0o644); err != nil {
+		t.Fatal(err)
+	}
+	if err := genConfig([]string{path}); err == nil || !strings.Contains(err.Error(), "-force") {
+		t.Errorf("gen-config over an existing file = %v, want it refused", err)
+	}
+	if buf, _ := os.ReadFile(path); string(buf) != "edited" {
+		t.Errorf("refused gen-config still wrote %q", buf)
+	}
+	if err := genConfig([]string{"-force", path}); err != nil {
+		t.Errorf("gen-config -force: %v", err)
+	}
+	if _, err := loadConfig(path); err != nil {
+		t.Errorf("config written with -force doesn't load: %v", err)
+	}
+}
+
+func TestValidateLayout(t *testing.T) {
+	tests := []struct {
+		name     string
+		services int
+		offset   int
+		reverse  bool
+		err      bool
+	}{
+		{name: "fits", services: 3, offset: 2},
+		{name: "fits reversed", services: 3, offset: 2, reverse: true},
+		{name: "off the end", services: 3, offset: 3, err: true},
+		{name: "off the end reversed", services: 3, offset: 3, reverse: true, err: true},
+	}
+	for _, tt := range tests {
+		c := DefaultConfig()
+		c.Services = nil
+		for i := 0; i < tt.services; i++ {
+			c.Services = append(c.Services, Service{Unit: fmt.Sprintf("unit-%d.service", i)})
+		}
+		c.Strip.StartOffset = tt.offset
+		c.Strip.Reverse = tt.reverse
+		err := c.Validate()
+		if (err != nil) != tt.err {
+			t.Errorf("%s: Validate() = %v, want an error: %t", tt.name, err, tt.err)
+		}
+	}
+}
+
+func TestResolvePalette(t *testing.T) {
+	file := filepath.Join(t.TempDir(), "palette.yaml")
+	if err := os.WriteFile(file, []byte("warm: \"111111\"\nCold: 0000ff\n"), 0o644); err != nil {
+		t.Fatal(err)
+	}
+	var c Config
+	c.PaletteFile = file
+	c.Palette = map[string]string{"warm": "ff8000"}
+	c.Strip.Colours = map[string]string{"failed": "palette:warm", "inactive": "palette:cold", "active": "00ff00"}
+	c.Services = []Service{{Unit: "a.service", States: map[string]string{"active": "palette:Warm"}}}
+	if err := c.ResolvePalette(); err != nil {
+		t.Fatalf("ResolvePalette: %v", err)
+	}
+	want := map[string]string{"failed": "ff8000", "inactive": "0000ff", "active": "00ff00"}
+	if !reflect.DeepEqual(c.Strip.Colours, want) {
+		t.Errorf("colours = %v, want %v with the inline palette beating the file", c.Strip.Colours, want)
+	}
+	if got := c.Services[0].States["active"]; got != "ff8000" {
+		t.Errorf("states_map active = %q, want ff8000", got)
+	}
+
+	c.Strip.Colours = map[string]string{"failed": "palette:nope"}
+	if err := c.ResolvePalette(); err == nil || !strings.Contains(err.Error(), "nope") {
+		t.Errorf("ResolvePalette with an unknown entry = %v, want an error naming it", err)
+	}
+}
+
+func TestPrintResolved(t *testing.T) {
+	path := filepath.Join(t.TempDir(), "config.yaml")
+	config := `
+palette:
+  warm: ff8000
+strip:
+  length: 4
+  channels: 3
+  hertz: 10
+  colours:
+    failed: palette:warm
+services:
+  - name: a.service
+    states_map:
+      active: palette:warm
+  - name: b.service
+    colours_from: a.service
+`
+	if err := os.WriteFile(path, []byte(config), 0o644); err != nil {
+		t.Fatal(err)
+	}
+	c, err := loadConfig(path)
+	if err != nil {
+		t.Fatalf("loadConfig: %v", err)
+	}
+	withConfig(t, c)
+	var out strings.Builder
+	if err := printResolved(&out); err != nil {
+		t.Fatalf("printResolved: %v", err)
+	}
+	if strings.Contains(out.String(), palettePrefix) {
+		t.Errorf("resolved output still names the palette:\n%s", out.String())
+	}
+	for _, want := range []string{"a.service\n", "b.service\n", "  active               ff8000\n", "  failed               ff8000\n"} {
+		if !strings.Contains(out.String(), want) {
+			t.Errorf("resolved output has no %q:\n%s", want, out.String())
+		}
+	}
+	_, b, _ := strings.Cut(out.String(), "b.service\n")
+	if !strings.Contains(b, "  active               ff8000\n") {
+		t.Errorf("b.service doesn't show the active colour it takes from a.service:\n%s", b)
+	}
+}
+
+func TestLoadConfigLength(t *testing.T) {
+	tests := []struct {
+		length   int
+		services int
+		err      bool
+	}{
+		{length: 5, services: 2},
+		{length: 255, services: 2},
+		{length: 300, services: 2},
+		{length: 300, services: 300},
+		{length: 300, services: 301, err: true},
+		{length: 1, services: 2, err: true},
+	}
+	for _, tt := range tests {
+		config := fmt.Sprintf("strip:\n  length: %d\n  channels: 3\n  hertz: 10\nservices:\n", tt.length)
+		for i := 0; i < tt.services; i++ {
+			config += fmt.Sprintf("  - name: unit-%d.service\n", i)
+		}
+		path := filepath.Join(t.TempDir(), "config.yaml")
+		if err := os.WriteFile(path, []byte(config), 0o644); err != nil {
+			t.Fatal(err)
+		}
+		c, err := loadConfig(path)
+		if (err != nil) != tt.err {
+			t.Errorf("length %d with %d services: loadConfig = %v, want an error: %t", tt.length, tt.services, err, tt.err)
+		}
+		if err == nil && c.Strip.Length != tt.length {
+			t.Errorf("length %d read as %d", tt.length, c.Strip.Length)
+		}
+	}
+}
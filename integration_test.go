@@ -0,0 +1,305 @@
+package main
+
+import (
+	"bytes"
+	"context"
+	"errors"
+	"sync"
+	"testing"
+	"time"
+
+	systemd "github.com/coreos/go-systemd/v22/dbus" // change namespace
+	"github.com/godbus/dbus/v5"                     // namespace collides with systemd wrapper
+	"github.com/shift/systemd-status-leds/led"
+	"github.com/shift/systemd-status-leds/strip"
+	"periph.io/x/conn/v3/physic"
+)
+
+// fakeSystemd is a scripted systemdConn. Units change through set and
+// remove, and every subscriber hears about it as it would from polling, or
+// through setQuietly, which nobody hears about until they resync.
+type fakeSystemd struct {
+	sync.Mutex
+	units       map[string]systemd.UnitStatus
+	results     map[string]string // Result of failed units
+	subscribers []fakeSubscriber
+	lookupErr   error // returned by GetUnitProperty when set
+}
+
+type fakeSubscriber struct {
+	updates chan map[string]*systemd.UnitStatus
+	errors  chan error
+	filter  func(string) bool
+}
+
+func newFakeSystemd() *fakeSystemd {
+	return &fakeSystemd{units: make(map[string]systemd.UnitStatus), results: make(map[string]string)}
+}
+
+func (f *fakeSystemd) set(unit string, state string, sub string, result string) {
+	status := f.setQuietly(unit, state, sub, result)
+	f.send(unit, &status)
+}
+
+func (f *fakeSystemd) setQuietly(unit string, state string, sub string, result string) systemd.UnitStatus {
+	f.Lock()
+	defer f.Unlock()
+	status := systemd.UnitStatus{Name: unit, LoadState: "loaded", ActiveState: state, SubState: sub}
+	f.units[unit] = status
+	f.results[unit] = result
+	return status
+}
+
+func (f *fakeSystemd) remove(unit string) {
+	f.Lock()
+	delete(f.units, unit)
+	f.Unlock()
+	f.send(unit, nil)
+}
+
+func (f *fakeSystemd) send(unit string, status *systemd.UnitStatus) {
+	f.Lock()
+	subscribers := append([]fakeSubscriber(nil), f.subscribers...)
+	f.Unlock()
+	for _, s := range subscribers {
+		if !s.filter(unit) {
+			s.updates <- map[string]*systemd.UnitStatus{unit: status}
+		}
+	}
+}
+
+// fail reports err to every subscriber, as a failed poll would.
+func (f *fakeSystemd) fail(err error) {
+	f.Lock()
+	subscribers := append([]fakeSubscriber(nil), f.subscribers...)
+	f.Unlock()
+	for _, s := range subscribers {
+		s.errors <- err
+	}
+}
+
+func (f *fakeSystemd) ListUnits() ([]systemd.UnitStatus, error) {
+	f.Lock()
+	defer f.Unlock()
+	var units []systemd.UnitStatus
+	for _, unit := range f.units {
+		units = append(units, unit)
+	}
+	return units, nil
+}
+
+func (f *fakeSystemd) GetUnitProperties(unit string) (map[string]interface{}, error) {
+	f.Lock()
+	defer f.Unlock()
+	status, ok := f.units[unit]
+	if !ok {
+		return map[string]interface{}{"LoadState": "not-found", "ActiveState": "inactive", "SubState": "dead"}, nil
+	}
+	return map[string]interface{}{"LoadState": status.LoadState, "ActiveState": status.ActiveState, "SubState": status.SubState}, nil
+}
+
+func (f *fakeSystemd) GetUnitProperty(unit string, propertyName string) (*systemd.Property, error) {
+	f.Lock()
+	err := f.lookupErr
+	f.Unlock()
+	if err != nil {
+		return nil, err
+	}
+	properties, _ := f.GetUnitProperties(unit)
+	return &systemd.Property{Name: propertyName, Value: dbus.MakeVariant(properties[propertyName])}, nil
+}
+
+func (f *fakeSystemd) GetUnitTypeProperty(unit string, unitType string, propertyName string) (*systemd.Property, error) {
+	f.Lock()
+	defer f.Unlock()
+	return &systemd.Property{Name: propertyName, Value: dbus.MakeVariant(f.results[unit])}, nil
+}
+
+func (f *fakeSystemd) SubscribeUnitsCustom(interval time.Duration, buffer int, isChanged func(*systemd.UnitStatus, *systemd.UnitStatus) bool, filterUnit func(string) bool) (<-chan map[string]*systemd.UnitStatus, <-chan error) {
+	f.Lock()
+	defer f.Unlock()
+	s := fakeSubscriber{updates: make(chan map[string]*systemd.UnitStatus, 16), errors: make(chan error, 1), filter: filterUnit}
+	f.subscribers = append(f.subscribers, s)
+	return s.updates, s.errors
+}
+
+// fakeSet is a unitSet without a systemd connection behind it.
+type fakeSet struct {
+	sync.Mutex
+	units map[string]bool
+}
+
+func (s *fakeSet) Add(unit string) {
+	s.Lock()
+	defer s.Unlock()
+	s.units[unit] = true
+}
+
+func (s *fakeSet) Remove(unit string) {
+	s.Lock()
+	defer s.Unlock()
+	delete(s.units, unit)
+}
+
+func (s *fakeSet) Contains(unit string) bool {
+	s.Lock()
+	defer s.Unlock()
+	return s.units[unit]
+}
+
+// recorder stands in for the SPI display, keeping every frame written.
+type recorder struct {
+	sync.Mutex
+	frames [][]byte
+}
+
+func (r *recorder) Write(buf []byte) (int, error) {
+	r.Lock()
+	defer r.Unlock()
+	r.frames = append(r.frames, append([]byte(nil), buf...))
+	return len(buf), nil
+}
+
+func (r *recorder) last() []byte {
+	r.Lock()
+	defer r.Unlock()
+	if len(r.frames) == 0 {
+		return nil
+	}
+	return r.frames[len(r.frames)-1]
+}
+
+// waitForFrame writes frames until the strip shows want, failing the test if
+// it doesn't within a few seconds.
+func waitForFrame(t *testing.T, s *strip.Strip, display *recorder, want []byte) {
+	t.Helper()
+	deadline := time.Now().Add(5 * time.Second)
+	for {
+		if err := s.Update(); err != nil {
+			t.Fatalf("Update: %v", err)
+		}
+		got := display.last()
+		if bytes.Equal(got, want) {
+			return
+		}
+		if time.Now().After(deadline) {
+			t.Fatalf("frame = %x, want %x", got, want)
+		}
+		time.Sleep(10 * time.Millisecond)
+	}
+}
+
+func TestServicesEndToEnd(t *testing.T) {
+	saved := C
+	defer func() { C = saved }()
+	C = Config{}
+	C.Strip = strip.Config{
+		Length:     3,
+		Channels:   3,
+		Hertz:      10,
+		SpiSpeedHz: int(strip.DefaultSpiSpeed / physic.Hertz),
+		SpiMode:    strip.DefaultSpiMode,
+		SpiBits:    strip.DefaultSpiBits,
+		Colours: map[string]string{
+			"active":         "00ff00",
+			"inactive":       "000011",
+			"failed":         "ff0000",
+			"failed/timeout": "ff8000",
+			"gone":           "222222",
+		},
+	}
+	C.Strip.Loading.Colour = "0a0a0a"
+	C.Strip.Loading.Style = "solid"
+
+	conn := newFakeSystemd()
+	conn.set("a.service", "active", "running", "")
+	conn.set("b.service", "inactive", "dead", "")
+	set := &fakeSet{units: make(map[string]bool)}
+
+	display := &recorder{}
+	s, err := strip.New(nil, &C.Strip, display)
+	if err != nil {
+		t.Fatalf("strip.New: %v", err)
+	}
+	waitForFrame(t, s, display, []byte{0x0a, 0x0a, 0x0a, 0x0a, 0x0a, 0x0a, 0x0a, 0x0a, 0x0a})
+
+	// The services must be done with C before it is restored.
+	ctx, cancel := context.WithCancel(context.Background())
+	var services sync.WaitGroup
+	defer func() {
+		cancel()
+		services.Wait()
+	}()
+	for _, unit := range []string{"a.service", "b.service"} {
+		pixel, err := s.Add(unit)
+		if err != nil {
+			t.Fatalf("Add(%s): %v", unit, err)
+		}
+		services.Add(1)
+		go func(unit string) {
+			defer services.Done()
+			addService(ctx, conn, set, Service{Unit: unit}, pixel)
+		}(unit)
+	}
+
+	steps := []struct {
+		name   string
+		change func()
+		want   []byte
+	}{
+		{"first states", func() {}, []byte{0, 0xff, 0, 0, 0, 0x11, 0, 0, 0}},
+		{"b starts", func() { conn.set("b.service", "active", "running", "") }, []byte{0, 0xff, 0, 0, 0xff, 0, 0, 0, 0}},
+		{"a fails", func() { conn.set("a.service", "failed", "failed", "exit-code") }, []byte{0xff, 0, 0, 0, 0xff, 0, 0, 0, 0}},
+		{"a times out", func() { conn.set("a.service", "failed", "failed", "timeout") }, []byte{0xff, 0x80, 0, 0, 0xff, 0, 0, 0, 0}},
+		{"b goes", func() { conn.remove("b.service") }, []byte{0xff, 0x80, 0, 0x22, 0x22, 0x22, 0, 0, 0}},
+		{"a recovers", func() { conn.set("a.service", "active", "running", "") }, []byte{0, 0xff, 0, 0x22, 0x22, 0x22, 0, 0, 0}},
+		{"a fails unheard until polling fails", func() {
+			conn.setQuietly("a.service", "failed", "failed", "exit-code")
+			conn.fail(errors.New("poll failed"))
+		}, []byte{0xff, 0, 0, 0x22, 0x22, 0x22, 0, 0, 0}},
+	}
+	for _, step := range steps {
+		step.change()
+		t.Log(step.name)
+		waitForFrame(t, s, display, step.want)
+	}
+}
+
+// waitForColour fails the test unless pixel shows want within a few seconds.
+func waitForColour(t *testing.T, pixel *led.Led, want string) {
+	t.Helper()
+	deadline := time.Now().Add(5 * time.Second)
+	for pixel.CurrentColour() != want {
+		if time.Now().After(deadline) {
+			t.Fatalf("%s is %s in %q, want %q", pixel.Unit, pixel.CurrentStatus(), pixel.CurrentColour(), want)
+		}
+		time.Sleep(10 * time.Millisecond)
+	}
+}
+
+func TestTimerUnit(t *testing.T) {
+	var c Config
+	c.Strip.Colours = map[string]string{"active": "00ff00", "active/waiting": "002200"}
+	withConfig(t, c)
+
+	conn := newFakeSystemd()
+	conn.set("backup.timer", "active", "waiting", "")
+	set := &fakeSet{units: make(map[string]bool)}
+	pixel := &led.Led{Unit: "backup.timer"}
+	ctx, cancel := context.WithCancel(context.Background())
+	done := make(chan struct{})
+	defer func() {
+		cancel()
+		<-done
+	}()
+	go func() {
+		defer close(done)
+		addService(ctx, conn, set, Service{Unit: "backup.timer"}, pixel)
+	}()
+
+	waitForColour(t, pixel, "002200")
+	conn.set("backup.timer", "active", "running", "")
+	waitForColour(t, pixel, "00ff00")
+	conn.set("backup.timer", "active", "waiting", "")
+	waitForColour(t, pixel, "002200")
+}
@@ -0,0 +1,56 @@
+package led
+
+import (
+	"math"
+	"time"
+
+	"github.com/shift/systemd-status-leds/colour"
+)
+
+// Animation varies an LED's colour over time.
+type Animation interface {
+	// Apply returns how base looks elapsed into the animation.
+	Apply(base colour.Colour, elapsed time.Duration) colour.Colour
+}
+
+// Pulse breathes between MinScale and MaxScale of full brightness, starting
+// at the dim end and taking Period for each breath.
+type Pulse struct {
+	MinScale float64       `mapstructure:"min_scale" yaml:"min_scale"`
+	MaxScale float64       `mapstructure:"max_scale" yaml:"max_scale"`
+	Period   time.Duration `mapstructure:"period" yaml:"period"`
+}
+
+// Scale is the fraction of full brightness elapsed into the pulse.
+func (p *Pulse) Scale(elapsed time.Duration) float64 {
+	if p.Period <= 0 {
+		return p.MaxScale
+	}
+	phase := 2 * math.Pi * float64(elapsed%p.Period) / float64(p.Period)
+	return p.MinScale + (p.MaxScale-p.MinScale)*(1-math.Cos(phase))/2
+}
+
+func (p *Pulse) Apply(base colour.Colour, elapsed time.Duration) colour.Colour {
+	return base.Scale(p.Scale(elapsed))
+}
+
+// Ramp moves from the LED's colour towards To over Duration, and then stays
+// there. Progress is exponential, so the change is slow at first and
+// quickens as the end nears.
+type Ramp struct {
+	To       colour.Colour
+	Duration time.Duration
+}
+
+// Progress is how far along the ramp is elapsed into it, from 0 to 1.
+func (r *Ramp) Progress(elapsed time.Duration) float64 {
+	if r.Duration <= 0 || elapsed >= r.Duration {
+		return 1
+	}
+	t := float64(elapsed) / float64(r.Duration)
+	return math.Expm1(3*t) / math.Expm1(3)
+}
+
+func (r *Ramp) Apply(base colour.Colour, elapsed time.Duration) colour.Colour {
+	return base.Mix(r.To, r.Progress(elapsed))
+}
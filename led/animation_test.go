@@ -0,0 +1,55 @@
+package led
+
+import (
+	"math"
+	"testing"
+	"time"
+
+	"github.com/shift/systemd-status-leds/colour"
+)
+
+func TestPulseScale(t *testing.T) {
+	pulse := Pulse{MinScale: 0.2, MaxScale: 1, Period: 2 * time.Second}
+	tests := []struct {
+		name    string
+		pulse   Pulse
+		elapsed time.Duration
+		want    float64
+	}{
+		{name: "starts dim", pulse: pulse, elapsed: 0, want: 0.2},
+		{name: "quarter way", pulse: pulse, elapsed: 500 * time.Millisecond, want: 0.6},
+		{name: "brightest halfway", pulse: pulse, elapsed: time.Second, want: 1},
+		{name: "three quarters", pulse: pulse, elapsed: 1500 * time.Millisecond, want: 0.6},
+		{name: "next breath", pulse: pulse, elapsed: 2 * time.Second, want: 0.2},
+		{name: "wraps", pulse: pulse, elapsed: 5 * time.Second, want: 1},
+		{name: "no period is steady", pulse: Pulse{MinScale: 0.2, MaxScale: 0.8}, elapsed: time.Second, want: 0.8},
+		{name: "negative period is steady", pulse: Pulse{MinScale: 0.2, MaxScale: 0.8, Period: -time.Second}, elapsed: time.Second, want: 0.8},
+		{name: "flat", pulse: Pulse{MinScale: 0.5, MaxScale: 0.5, Period: time.Second}, elapsed: 300 * time.Millisecond, want: 0.5},
+	}
+	for _, tt := range tests {
+		if got := tt.pulse.Scale(tt.elapsed); math.Abs(got-tt.want) > 1e-9 {
+			t.Errorf("%s: Scale(%s) = %g, want %g", tt.name, tt.elapsed, got, tt.want)
+		}
+	}
+}
+
+func TestRampApply(t *testing.T) {
+	from := colour.Colour{R: 0xff, G: 0x10}
+	ramp := Ramp{To: colour.Colour{R: 0x10, G: 0xff, W: 0x80}, Duration: 2 * time.Second}
+	if got := ramp.Apply(from, 0); got != from {
+		t.Errorf("Apply at the start = %v, want %v", got, from)
+	}
+	if got := ramp.Apply(from, ramp.Duration); got != ramp.To {
+		t.Errorf("Apply at the end = %v, want %v", got, ramp.To)
+	}
+	mid := ramp.Apply(from, ramp.Duration/2)
+	between := func(v, a, b byte) bool {
+		if a > b {
+			a, b = b, a
+		}
+		return a < v && v < b
+	}
+	if !between(mid.R, from.R, ramp.To.R) || !between(mid.G, from.G, ramp.To.G) || !between(mid.W, from.W, ramp.To.W) {
+		t.Errorf("Apply halfway = %v, want each channel between %v and %v", mid, from, ramp.To)
+	}
+}
@@ -2,8 +2,55 @@ package led
 
 import (
 	"sync"
+	"time"
+
+	"github.com/shift/systemd-status-leds/colour"
 )
 
+// DefaultColours are the built in colours for each unit state, used when a
+// config doesn't choose its own.
+var DefaultColours = map[string]string{
+	"active":       "00ff0000",
+	"inactive":     "01010101",
+	"reloading":    "11551100",
+	"failed":       "55002200",
+	"activating":   "00442200",
+	"deactivating": "22440000",
+	"gone":         "10000010",
+	"unknown":      "20002000",
+	"masked":       "00001010",
+	"error":        "44000000",
+	"bad-setting":  "44000000",
+	"stuck":        "66220000",
+}
+
+// severities orders the unit states from healthy to broken.
+var severities = map[string]int{
+	"active":       0,
+	"inactive":     1,
+	"reloading":    2,
+	"activating":   3,
+	"deactivating": 4,
+	"gone":         5,
+	"failed":       6,
+}
+
+// Severity ranks a unit state, higher being worse, so everything comparing
+// states agrees: failed > gone > deactivating > activating > reloading >
+// inactive > active. States it doesn't know rank alongside gone.
+func Severity(state string) int {
+	if severity, ok := severities[state]; ok {
+		return severity
+	}
+	return severities["gone"]
+}
+
+// DefaultColour returns the built in colour for a unit state.
+func DefaultColour(state string) (string, bool) {
+	colour, ok := DefaultColours[state]
+	return colour, ok
+}
+
 type Led struct {
 	sync.RWMutex
 	Red    int64
@@ -13,29 +60,205 @@ type Led struct {
 	Colour string
 	Number int
 	Unit   string
+	Label  string // friendly name for status output, Unit if empty
 	Status string
+
+	Animation Animation   // nil for a steady colour
+	since     time.Time   // when the status last changed
+	previous  string      // the status before, empty until it first changes
+	heldUntil time.Time   // earliest another state may be shown
+	pending   *time.Timer // state waiting to be applied
+	driven    bool        // colour set directly by Drive rather than a state
+}
+
+func (l *Led) SetLabel(label string) {
+	l.Lock()
+	defer l.Unlock()
+	l.Label = label
 }
 
 func (l *Led) SetStatus(state string) {
+	l.Lock()
+	defer l.Unlock()
+	if l.Status != state {
+		l.since = time.Now()
+		l.previous = l.Status
+	}
 	l.Status = state
 }
 
+// TimeInState is how long the LED has had its current status, re-applying the
+// same status doesn't reset it.
+func (l *Led) TimeInState() time.Duration {
+	l.RLock()
+	defer l.RUnlock()
+	if l.since.IsZero() {
+		return 0
+	}
+	return time.Since(l.since)
+}
+
+// IsAnimating reports whether the LED is changing by itself rather than
+// showing a steady colour. A ramp that has arrived counts as steady.
+func (l *Led) IsAnimating() bool {
+	l.RLock()
+	defer l.RUnlock()
+	if ramp, ok := l.Animation.(*Ramp); ok {
+		return time.Since(l.since) < ramp.Duration
+	}
+	return l.Animation != nil
+}
+
 func (l *Led) SetRed(r int64) {
+	l.Lock()
+	defer l.Unlock()
 	l.Red = r
 }
 
 func (l *Led) SetGreen(g int64) {
+	l.Lock()
+	defer l.Unlock()
 	l.Green = g
 }
 
 func (l *Led) SetBlue(b int64) {
+	l.Lock()
+	defer l.Unlock()
 	l.Blue = b
 }
 
 func (l *Led) SetWhite(w int64) {
+	l.Lock()
+	defer l.Unlock()
 	l.White = w
 }
 
 func (l *Led) SetColour(colour string) {
+	l.Lock()
+	defer l.Unlock()
 	l.Colour = colour
 }
+
+// Drive shows a steady colour set from outside the systemd subscription,
+// dropping any animation and any state still waiting to be applied.
+func (l *Led) Drive(colour string) {
+	l.Lock()
+	defer l.Unlock()
+	if l.pending != nil {
+		l.pending.Stop()
+		l.pending = nil
+	}
+	l.Colour = colour
+	l.Animation = nil
+	l.driven = true
+}
+
+// IsSet reports whether the LED has been given something to show, either a
+// unit state or a colour through Drive.
+func (l *Led) IsSet() bool {
+	l.RLock()
+	defer l.RUnlock()
+	return l.Status != "" || l.driven
+}
+
+// Look is how an LED shows a unit state.
+type Look struct {
+	Colour    string
+	Animation Animation     // nil for a steady colour
+	Hold      time.Duration // minimum time on show before another state
+}
+
+// ApplyState sets the status along with the look representing it, so readers
+// never see one without the other. While the previous look's hold hasn't run
+// out, the state waits for it instead, unless it is more severe.
+func (l *Led) ApplyState(state string, look Look) {
+	l.Lock()
+	defer l.Unlock()
+	l.apply(state, look)
+}
+
+// apply shows the state now unless a more severe one is still held, in which
+// case it waits for the hold to end. The caller holds the lock.
+func (l *Led) apply(state string, look Look) {
+	if wait := time.Until(l.heldUntil); wait > 0 && Severity(state) <= Severity(l.Status) {
+		l.schedule(wait, state, look)
+		return
+	}
+	// Whatever was waiting is older than this state, so it must not win.
+	if l.pending != nil {
+		l.pending.Stop()
+		l.pending = nil
+	}
+	if l.Status != state {
+		l.since = time.Now()
+		l.previous = l.Status
+	}
+	l.Status = state
+	l.Colour = look.Colour
+	l.Animation = look.Animation
+	l.heldUntil = time.Now().Add(look.Hold)
+}
+
+// ApplyStateAfter applies the state once it has held for d. A state arriving
+// while one is pending replaces it, so only the last of a burst is shown.
+func (l *Led) ApplyStateAfter(d time.Duration, state string, look Look) {
+	if d <= 0 {
+		l.ApplyState(state, look)
+		return
+	}
+	l.Lock()
+	defer l.Unlock()
+	l.schedule(d, state, look)
+}
+
+// schedule applies the state after d, replacing any state already waiting.
+// The caller holds the lock.
+func (l *Led) schedule(d time.Duration, state string, look Look) {
+	if l.pending != nil {
+		l.pending.Stop()
+	}
+	var timer *time.Timer
+	timer = time.AfterFunc(d, func() {
+		l.Lock()
+		defer l.Unlock()
+		if l.pending != timer {
+			return // replaced or overtaken while waiting for the lock
+		}
+		l.pending = nil
+		l.apply(state, look)
+	})
+	l.pending = timer
+}
+
+// CurrentStatus returns the unit state last applied, empty before the first.
+func (l *Led) CurrentStatus() string {
+	l.RLock()
+	defer l.RUnlock()
+	return l.Status
+}
+
+// PreviousStatus returns the status the LED had before the current one, empty
+// if there wasn't one.
+func (l *Led) PreviousStatus() string {
+	l.RLock()
+	defer l.RUnlock()
+	return l.previous
+}
+
+// CurrentColour returns the colour without any animation applied.
+func (l *Led) CurrentColour() string {
+	l.RLock()
+	defer l.RUnlock()
+	return l.Colour
+}
+
+// Frame returns the colour to show at now, with any animation applied.
+func (l *Led) Frame(now time.Time) colour.Colour {
+	l.RLock()
+	defer l.RUnlock()
+	c, _ := colour.Parse(l.Colour) // unset pixels stay off
+	if l.Animation == nil {
+		return c
+	}
+	return l.Animation.Apply(c, now.Sub(l.since))
+}
@@ -0,0 +1,120 @@
+package led
+
+import (
+	"testing"
+	"time"
+)
+
+func TestApplyState(t *testing.T) {
+	l := &Led{Unit: "a.service"}
+	l.ApplyState("active", Look{Colour: "00ff00"})
+	l.ApplyState("failed", Look{Colour: "ff0000"})
+	if l.CurrentStatus() != "failed" || l.CurrentColour() != "ff0000" {
+		t.Errorf("after failing = %s, %s, want failed, ff0000", l.CurrentStatus(), l.CurrentColour())
+	}
+	if !l.IsSet() {
+		t.Error("IsSet = false after a state was applied")
+	}
+}
+
+func TestApplyStateAfterBurst(t *testing.T) {
+	l := &Led{Unit: "a.service"}
+	l.ApplyState("activating", Look{Colour: "004422"})
+	l.ApplyStateAfter(20*time.Millisecond, "active", Look{Colour: "00ff00"})
+	l.ApplyStateAfter(20*time.Millisecond, "inactive", Look{Colour: "010101"})
+	l.ApplyStateAfter(20*time.Millisecond, "active", Look{Colour: "00ff11"})
+	if got := l.CurrentStatus(); got != "activating" {
+		t.Errorf("during the burst status = %s, want activating still", got)
+	}
+	time.Sleep(60 * time.Millisecond)
+	if l.CurrentStatus() != "active" || l.CurrentColour() != "00ff11" {
+		t.Errorf("after the burst = %s, %s, want the last, active, 00ff11", l.CurrentStatus(), l.CurrentColour())
+	}
+	if got := l.PreviousStatus(); got != "activating" {
+		t.Errorf("previous status = %q, want activating as inactive was never shown", got)
+	}
+}
+
+func TestApplyStateHold(t *testing.T) {
+	l := &Led{Unit: "a.service"}
+	l.ApplyState("failed", Look{Colour: "ff0000", Hold: 50 * time.Millisecond})
+	l.ApplyState("active", Look{Colour: "00ff00"})
+	if l.CurrentStatus() != "failed" || l.CurrentColour() != "ff0000" {
+		t.Errorf("within min_hold = %s, %s, want failed, ff0000", l.CurrentStatus(), l.CurrentColour())
+	}
+	time.Sleep(100 * time.Millisecond)
+	if l.CurrentStatus() != "active" || l.CurrentColour() != "00ff00" {
+		t.Errorf("after min_hold = %s, %s, want active, 00ff00", l.CurrentStatus(), l.CurrentColour())
+	}
+}
+
+func TestTimeInState(t *testing.T) {
+	l := &Led{Unit: "a.service"}
+	if got := l.TimeInState(); got != 0 {
+		t.Errorf("before any state TimeInState = %s, want 0", got)
+	}
+	l.ApplyState("activating", Look{Colour: "004422"})
+	time.Sleep(20 * time.Millisecond)
+	l.ApplyState("activating", Look{Colour: "004422"})
+	if got := l.TimeInState(); got < 20*time.Millisecond {
+		t.Errorf("after re-applying the same state TimeInState = %s, want at least 20ms", got)
+	}
+	l.ApplyState("active", Look{Colour: "00ff00"})
+	if got := l.TimeInState(); got >= 20*time.Millisecond {
+		t.Errorf("after a new state TimeInState = %s, want it reset", got)
+	}
+}
+
+func TestSeverity(t *testing.T) {
+	order := []string{"active", "inactive", "reloading", "activating", "deactivating", "gone", "failed"}
+	for i := 1; i < len(order); i++ {
+		if Severity(order[i]) <= Severity(order[i-1]) {
+			t.Errorf("Severity(%s) = %d, want more than Severity(%s) = %d", order[i], Severity(order[i]), order[i-1], Severity(order[i-1]))
+		}
+	}
+	if Severity("failed") <= Severity("activating") || Severity("activating") <= Severity("active") {
+		t.Error("want failed > activating > active")
+	}
+	if Severity("nonsense") != Severity("gone") {
+		t.Errorf("Severity of an unknown state = %d, want gone's %d", Severity("nonsense"), Severity("gone"))
+	}
+}
+
+func TestIsAnimating(t *testing.T) {
+	l := &Led{Unit: "a.service"}
+	l.ApplyState("active", Look{Colour: "00ff00"})
+	if l.IsAnimating() {
+		t.Error("steady colour IsAnimating = true")
+	}
+	l.ApplyState("failed", Look{Colour: "ff0000", Animation: &Pulse{MinScale: 0.2, MaxScale: 1, Period: time.Second}})
+	if !l.IsAnimating() {
+		t.Error("pulse IsAnimating = false")
+	}
+	l.ApplyState("activating", Look{Colour: "004422", Animation: &Ramp{Duration: 20 * time.Millisecond}})
+	if !l.IsAnimating() {
+		t.Error("ramp under way IsAnimating = false")
+	}
+	time.Sleep(40 * time.Millisecond)
+	if l.IsAnimating() {
+		t.Error("finished ramp IsAnimating = true")
+	}
+}
+
+func TestPreviousStatus(t *testing.T) {
+	l := &Led{Unit: "a.service"}
+	steps := []struct {
+		state    string
+		previous string
+	}{
+		{"activating", ""},
+		{"active", "activating"},
+		{"active", "activating"}, // the same state again changes nothing
+		{"failed", "active"},
+	}
+	for _, step := range steps {
+		l.ApplyState(step.state, Look{Colour: "ffffff"})
+		if got := l.PreviousStatus(); got != step.previous {
+			t.Errorf("after %s PreviousStatus = %q, want %q", step.state, got, step.previous)
+		}
+	}
+}
@@ -1,65 +1,101 @@
 package main // github.com/shift/systemd-status-leds
 
 import (
+	"context"
+	"errors"
+	"flag"
+	"fmt"
+	"net/http"
+	"os"
+	"os/signal"
+	"path"
+	"strings"
+	"sync"
+	"syscall"
+	"time"
+
 	systemd "github.com/coreos/go-systemd/v22/dbus" // change namespace
 	systemdUtil "github.com/coreos/go-systemd/v22/util"
 	"github.com/godbus/dbus/v5" // namespace collides with systemd wrapper
+	"github.com/shift/systemd-status-leds/colour"
 	"github.com/shift/systemd-status-leds/led"
+	"github.com/shift/systemd-status-leds/status"
 	"github.com/shift/systemd-status-leds/strip"
 
 	"github.com/jar-o/limlog"
-	"github.com/spf13/viper"
 	"go.uber.org/zap"
 )
 
-type Service struct {
-	Unit   string            `mapstructure:"name"`
-	States map[string]string `mapstrcture:"states_map"`
-}
-
-type Config struct {
-	Services []Service `mapstructure:"services"`
-	Strip    struct {
-		Length   int
-		Channels int
-		Hertz    int
-		Spidev   string
-		colours  map[string]string
-	}
-}
-
 var (
 	logr *limlog.Limlog
 	C    Config
+
+	configFile   = flag.String("config", "", "configuration file, JSON if it ends in .json and YAML otherwise (default ./config)")
+	statusSocket = flag.String("status-socket", "", "Unix socket serving a JSON snapshot of the LEDs")
+	webAddr      = flag.String("web-addr", "", "address to serve the status dashboard on, e.g. :8080")
+	dbusAddress  = flag.String("dbus-address", "", "D-Bus address to reach systemd on instead of the system bus")
+	dryRun       = flag.Bool("dry-run", false, "log frames at debug level instead of writing them to SPI")
+	logFormat    = flag.String("log-format", "", "log as text or json")
+	eventBuffer  = flag.Int("event-buffer", -1, "unit changes each subscriber may queue, overrides events.buffer")
+	frequency    = flag.Int("frequency", 0, "frames per second, overrides strip.hertz")
 )
 
-func Configuration() {
-	viper.SetConfigName("config")
-	viper.SetConfigType("yaml")
-	viper.AddConfigPath(".")
-	err := viper.ReadInConfig()
-	if err != nil {
-		logr.Panic("config file", zap.Error(err))
-	}
-	err = viper.Unmarshal(&C)
-	if err != nil {
-		logr.Panic("config file", zap.Error(err))
-	}
-}
+// How often glob services with watch enabled look for newly appeared units.
+const watchInterval = 30 * time.Second
+
+// How often systemd is polled for unit changes unless events.interval says.
+const defaultEventInterval = time.Second
+
+// Connecting to systemd is retried, backing off up to maxConnectWait between
+// attempts, as D-Bus may not be up yet early in boot.
+const (
+	defaultConnectRetry = 30 * time.Second
+	firstConnectWait    = 500 * time.Millisecond
+	maxConnectWait      = 10 * time.Second
+)
 
 func main() {
+	flag.Parse()
+
 	// First thigns first, logging...
 	cfg := limlog.NewZapConfigWithLevel(zap.DebugLevel)
+	if err := setLogFormat(&cfg, *logFormat); err != nil {
+		fmt.Fprintln(os.Stderr, err)
+		os.Exit(2)
+	}
 	logr = limlog.NewLimlogZapWithConfig(cfg)
 	z := logr.L.GetLogger().(*zap.Logger)
 	defer z.Sync()
 
+	if flag.Arg(0) == "gen-config" {
+		if err := genConfig(flag.Args()[1:]); err != nil {
+			fmt.Fprintln(os.Stderr, "gen-config:", err)
+			os.Exit(1)
+		}
+		return
+	}
+	if flag.Arg(0) == "list-units" {
+		if err := listUnits(flag.Args()[1:]); err != nil {
+			fmt.Fprintln(os.Stderr, "list-units:", err)
+			os.Exit(1)
+		}
+		return
+	}
 	Configuration()
+	if flag.Arg(0) == "validate" {
+		if err := printResolved(os.Stdout); err != nil {
+			fmt.Fprintln(os.Stderr, "validate:", err)
+			os.Exit(1)
+		}
+		return
+	}
+	applyFlags(&C)
 	z.Info("Strip",
 		zap.String("spidev", C.Strip.Spidev),
 		zap.Int("length", C.Strip.Length),
 		zap.Int("channels", C.Strip.Channels),
 		zap.Int("hertz", C.Strip.Hertz),
+		zap.Int("spi_speed_hz", C.Strip.SpiSpeedHz),
 	)
 	for _, service := range C.Services {
 		z.Info("Service",
@@ -67,66 +103,598 @@ func main() {
 		)
 	}
 
-	strip, err := strip.Init(logr, &C.Strip.Spidev, &C.Strip.Length, &C.Strip.Channels, &C.Strip.Hertz)
+	strip, err := strip.Init(logr, &C.Strip, *dryRun)
 
 	if err != nil {
 		logr.Panic("unable to initalise the strip", zap.Error(err))
 	}
 
-	if !systemdUtil.IsRunningSystemd() {
+	if code := os.Getenv("LEDS_FORCE_COLOR"); code != "" {
+		if _, err := colour.Parse(code); err != nil {
+			logr.Panic("LEDS_FORCE_COLOR", zap.Error(err))
+		}
+		logr.Warn("LEDS_FORCE_COLOR is set, every LED shows it", zap.String("colour", code))
+		strip.Force(code)
+	}
+
+	if C.DBus.Address == "" && !systemdUtil.IsRunningSystemd() {
 		logr.Panic("systemd is not running", zap.Error(err))
 	}
 
-	conn, err := systemd.New()
+	dial := func() (*systemd.Conn, error) { return connect(C.DBus.Address) }
+	conn, err := connectRetrying(dial, C.DBus.Retry, time.Now, time.Sleep)
 
 	if err != nil {
 		logr.Panic("systemd unable to connect, running as root?", zap.Error(err))
 	}
-	err = conn.Subscribe()
+	set := conn.NewSubscriptionSet() // no error should be returned
+	mode, err := chooseMonitor(C.MonitorMode, conn.Subscribe)
 	if err != nil {
 		logr.Panic("systemd subscribed failed", zap.Error(err))
 	}
-	set := conn.NewSubscriptionSet() // no error should be returned
+	if mode != pollUnits {
+		hub = startSignals(conn, set, mode == signalOrPoll)
+	}
+	ctx, stop := signal.NotifyContext(context.Background(), syscall.SIGINT, syscall.SIGTERM)
+	defer stop()
+	known, err := unitNames(conn)
+	if err != nil {
+		logr.Error("systemd unable to list units", zap.Error(err))
+	}
+	// Named units go first, so globs only take the pixels left over instead of
+	// leaving a service listed after them without one.
+	var globs []Service
+	named := make(map[string]bool)
 	for _, service := range C.Services {
+		service.Unit = canonicalUnit(service.Unit, known)
+		if isGlob(service.Unit) {
+			globs = append(globs, service)
+			continue
+		}
+		add, err := checkMissing(conn, service.Unit, C.MissingUnits)
+		if err != nil {
+			logr.Panic("Unit not found", zap.Error(err))
+		}
+		if !add {
+			continue
+		}
 		pixel, err := strip.Add(service.Unit)
 		if err != nil {
 			logr.Panic("Error calling Strip.Add:", zap.Error(err))
 		}
-		go addService(conn, set, pixel)
+		pixel.SetLabel(service.Label)
+		named[service.Unit] = true
+		go addService(ctx, conn, set, service, pixel)
+	}
+	for _, service := range globs {
+		seen := make(map[string]bool, len(named))
+		for unit := range named {
+			seen[unit] = true // already shown by its own service
+		}
+		err = addMatching(ctx, conn, set, strip, service, seen)
+		if err != nil {
+			logr.Panic("systemd unable to list units", zap.Error(err))
+		}
+		if service.Watch {
+			go watchMatching(ctx, conn, set, strip, service, seen)
+		}
+	}
+
+	if C.Watchdog.Timeout > 0 {
+		alive()
+		go watchdog(strip)
+	}
+	if C.Status.Socket != "" {
+		go func() {
+			if err := status.Serve(C.Status.Socket, strip); err != nil {
+				logr.Error("Status socket failed", zap.Error(err))
+			}
+		}()
+	}
+	if C.Status.WebAddr != "" {
+		go func() {
+			if err := http.ListenAndServe(C.Status.WebAddr, status.Handler(strip)); err != nil {
+				logr.Error("Status dashboard failed", zap.Error(err))
+			}
+		}()
+	}
+
+	if err := strip.Run(ctx); err != nil {
+		logr.Error("Failed to turn the strip off", zap.Error(err))
 	}
-	strip.UpdateLoop()
 
 }
 
-func addService(conn *systemd.Conn, set *systemd.SubscriptionSet, pixelRef *led.Led) {
-	subChannel, subErrors := set.Subscribe()
-	pixel := *pixelRef
-	var svc = pixel.Unit
-	var activeSet = false
-	var invalid = false
-	var previous bool
-	for {
-		previous = invalid
-		invalid = false
-		loadstate, err := conn.GetUnitProperty(svc, "LoadState")
+// applyFlags overrides the config with the flags given on the command line.
+func applyFlags(c *Config) {
+	if *statusSocket != "" {
+		c.Status.Socket = *statusSocket
+	}
+	if *webAddr != "" {
+		c.Status.WebAddr = *webAddr
+	}
+	if *dbusAddress != "" {
+		c.DBus.Address = *dbusAddress
+	}
+	if *eventBuffer >= 0 {
+		c.Events.Buffer = *eventBuffer
+	}
+	if *frequency != 0 {
+		c.Strip.Hertz = *frequency // checked with the rest of the strip by Init
+	}
+}
+
+// logEncodings maps -log-format values to zap encodings.
+var logEncodings = map[string]string{
+	"text": "console",
+	"json": "json",
+}
+
+// setLogFormat switches the log encoding, leaving it alone if format is empty.
+func setLogFormat(cfg *zap.Config, format string) error {
+	if format == "" {
+		return nil
+	}
+	encoding, ok := logEncodings[format]
+	if !ok {
+		return fmt.Errorf("unknown log format %q, want text or json", format)
+	}
+	cfg.Encoding = encoding
+	return nil
+}
+
+// isGlob reports whether a configured unit name is a pattern to be expanded
+// against the units systemd knows about.
+func isGlob(name string) bool {
+	return strings.ContainsAny(name, "*?[")
+}
+
+// matchUnits returns the units matching the glob pattern, keeping their order.
+func matchUnits(pattern string, units []string) []string {
+	var matched []string
+	for _, unit := range units {
+		if ok, _ := path.Match(pattern, unit); ok {
+			matched = append(matched, unit)
+		}
+	}
+	return matched
+}
+
+// unitNames lists the names of the units systemd has loaded.
+func unitNames(conn systemdConn) ([]string, error) {
+	units, err := conn.ListUnits()
+	if err != nil {
+		return nil, err
+	}
+	names := make([]string, 0, len(units))
+	for _, unit := range units {
+		names = append(names, unit.Name)
+	}
+	return names, nil
+}
+
+// canonicalUnit returns the unit name as systemd spells it, so a configured
+// SSHD.service still matches events for sshd.service. Names it doesn't know
+// are returned trimmed but otherwise as written.
+func canonicalUnit(name string, known []string) string {
+	name = strings.TrimSpace(name)
+	for _, unit := range known {
+		if unit == name {
+			return unit
+		}
+	}
+	for _, unit := range known {
+		if strings.EqualFold(unit, name) {
+			return unit
+		}
+	}
+	return name
+}
+
+// addMatching adds a pixel for every unit matching the service's pattern which
+// hasn't been seen yet, stopping quietly once the strip has no pixels left.
+// The pixels follow their units until ctx is done.
+func addMatching(ctx context.Context, conn systemdConn, set unitSet, s *strip.Strip, service Service, seen map[string]bool) error {
+	names, err := unitNames(conn)
+	if err != nil {
+		return err
+	}
+	for _, name := range matchUnits(service.Unit, names) {
+		if seen[name] {
+			continue
+		}
+		pixel, err := s.Add(name)
+		if errors.Is(err, strip.ErrFull) {
+			logr.Warn("No pixel left for unit", zap.String("pattern", service.Unit), zap.String("name", name))
+			return nil
+		}
 		if err != nil {
-			logr.Error("Failed to get property:", zap.Error(err))
-			invalid = true
+			return err
 		}
+		seen[name] = true
+		go addService(ctx, conn, set, service, pixel)
+	}
+	return nil
+}
+
+func watchMatching(ctx context.Context, conn systemdConn, set unitSet, s *strip.Strip, service Service, seen map[string]bool) {
+	for {
+		select {
+		case <-ctx.Done():
+			return
+		case <-time.After(watchInterval):
+		}
+		if err := addMatching(ctx, conn, set, s, service, seen); err != nil {
+			logr.Error("Failed to expand unit pattern", zap.String("pattern", service.Unit), zap.Error(err))
+		}
+	}
+}
 
-		if !invalid {
-			var notFound = (loadstate.Value == dbus.MakeVariant("not-found"))
-			if notFound {
-				logr.Info("Failed to find service")
-				invalid = true
+// resolve decides what an LED shows for a unit's state, and is the only place
+// choosing between overlapping settings. The colour comes from, highest
+// priority first:
+//
+//  1. the service's own states_map
+//  2. the strip wide colours, skipped with inherit: false
+//  3. the built in colours
+//
+// Within each, a key naming the sub state as well, such as "active/waiting"
+// for a timer waiting to elapse, beats the plain active state. For failed
+// units the result takes the sub state's place, as in "failed/timeout". A
+// pulse configured for the state then animates whichever colour won, and its
+// min_hold keeps it on show for a while whatever comes next. With an
+// activating_ramp, activating units ramp towards the active colour instead of
+// pulsing, with a deactivating_fade and animations enabled deactivating units
+// fade from the active colour to off, and inactive_off and problems_only
+// leave healthy units dark.
+func resolve(service Service, state string, sub string) (led.Look, bool) {
+	keys := stateKeys(state, sub)
+	code, ok := colourFor(service, keys)
+	if !ok {
+		code = unknownColour(service)
+	}
+	look := led.Look{Colour: code, Animation: animationFor(keys), Hold: holdFor(keys)}
+	if dark(state) {
+		return led.Look{Colour: off.String(), Hold: look.Hold}, true
+	}
+	if state == "activating" && C.Strip.ActivatingRamp > 0 {
+		active, _ := colourFor(service, []string{"active"})
+		to, _ := colour.Parse(active)
+		look.Animation = &led.Ramp{To: to, Duration: C.Strip.ActivatingRamp}
+	}
+	// Without animations the fade would never leave its starting colour, so a
+	// deactivating unit would look active.
+	if state == "deactivating" && C.Strip.DeactivateFade > 0 && !C.Strip.DisableAnimations {
+		look.Colour, _ = colourFor(service, []string{"active"})
+		look.Animation = &led.Ramp{To: off, Duration: C.Strip.DeactivateFade}
+	}
+	return look, ok
+}
+
+// off is shown for states that should leave the LED dark.
+var off colour.Colour
+
+// dark reports whether a state leaves the LED off: inactive with
+// inactive_off, and anything less severe than activating with problems_only.
+func dark(state string) bool {
+	if C.Strip.ProblemsOnly && led.Severity(state) < led.Severity("activating") {
+		return true
+	}
+	return state == "inactive" && C.Strip.InactiveOff
+}
+
+// stateKeys lists the keys a unit state may be configured under, most
+// specific first.
+func stateKeys(state string, sub string) []string {
+	if sub == "" {
+		return []string{state}
+	}
+	return []string{state + "/" + sub, state}
+}
+
+// colourFor resolves the colour for a unit state, preferring the service's own
+// states_map over the strip wide colours, which a service that doesn't
+// inherit skips. The built in defaults come last, so a config written before
+// a state such as gone or stuck existed still shows it.
+func colourFor(service Service, keys []string) (string, bool) {
+	for _, key := range keys {
+		if code, ok := service.States[key]; ok {
+			return code, true
+		}
+	}
+	if service.Inherits() {
+		for _, key := range keys {
+			if code, ok := C.Strip.Colours[key]; ok {
+				return code, true
 			}
 		}
+	}
+	for _, key := range keys {
+		if code, ok := led.DefaultColour(key); ok {
+			return code, true
+		}
+	}
+	return "", false
+}
+
+// unknownColour is shown for states nothing is configured for, so the pixel
+// doesn't just go dark.
+func unknownColour(service Service) string {
+	if code, ok := colourFor(service, []string{"unknown"}); ok {
+		return code
+	}
+	code, _ := led.DefaultColour("unknown")
+	return code
+}
+
+// animationFor returns the animation configured for a state, if any.
+func animationFor(keys []string) led.Animation {
+	for _, key := range keys {
+		if pulse, ok := C.Strip.Pulses[key]; ok {
+			return &pulse
+		}
+	}
+	return nil
+}
+
+// holdFor returns how long a state stays on show at least.
+func holdFor(keys []string) time.Duration {
+	for _, key := range keys {
+		if hold, ok := C.Strip.MinHold[key]; ok {
+			return hold
+		}
+	}
+	return 0
+}
+
+// systemdConn is what the daemon asks of systemd once connected, which
+// *systemd.Conn provides and tests script.
+type systemdConn interface {
+	ListUnits() ([]systemd.UnitStatus, error)
+	GetUnitProperties(unit string) (map[string]interface{}, error)
+	GetUnitProperty(unit string, propertyName string) (*systemd.Property, error)
+	GetUnitTypeProperty(unit string, unitType string, propertyName string) (*systemd.Property, error)
+	SubscribeUnitsCustom(interval time.Duration, buffer int, isChanged func(*systemd.UnitStatus, *systemd.UnitStatus) bool, filterUnit func(string) bool) (<-chan map[string]*systemd.UnitStatus, <-chan error)
+}
+
+// unitSet is the set of units the polling subscriptions report on, a
+// *systemd.SubscriptionSet outside tests.
+type unitSet interface {
+	Add(unit string)
+	Remove(unit string)
+	Contains(unit string) bool
+}
+
+// connect opens a connection to systemd on the system bus, or on the D-Bus
+// address given, such as unix:path=/run/dbus/system_bus_socket or, for the
+// units of another machine, tcp:host=localhost,port=55556 forwarded over SSH.
+func connect(address string) (*systemd.Conn, error) {
+	if address == "" {
+		return systemd.New()
+	}
+	conn, err := systemd.NewConnection(func() (*dbus.Conn, error) {
+		conn, err := dbus.Dial(address)
+		if err != nil {
+			return nil, err
+		}
+		if err = conn.Auth(nil); err != nil {
+			conn.Close()
+			return nil, err
+		}
+		if err = conn.Hello(); err != nil {
+			conn.Close()
+			return nil, err
+		}
+		return conn, nil
+	})
+	if err != nil {
+		return nil, fmt.Errorf("dbus %s: %w", address, err)
+	}
+	return conn, nil
+}
 
-		if previous != invalid { // if invalid changed, send signal
+// connectRetrying calls dial until it succeeds or retry has passed, while the
+// strip shows the loading pattern. The clock is read with now and waited on
+// with sleep.
+func connectRetrying(dial func() (*systemd.Conn, error), retry time.Duration, now func() time.Time, sleep func(time.Duration)) (*systemd.Conn, error) {
+	deadline := now().Add(retry)
+	wait := firstConnectWait
+	for {
+		conn, err := dial()
+		if err == nil || now().Add(wait).After(deadline) {
+			return conn, err
+		}
+		logr.Warn("systemd unable to connect, retrying", zap.Duration("in", wait), zap.Error(err))
+		sleep(wait)
+		if wait *= 2; wait > maxConnectWait {
+			wait = maxConnectWait
+		}
+	}
+}
+
+// subscribe is set.Subscribe with the channel buffer taken from the config.
+// Every caller gets a poller and channels of its own, so each sink, whether an
+// LED or anything else watching units, only ever lags itself.
+//
+// systemd's poller blocks until a subscriber takes each batch of changes, so
+// with no buffer one slow LED holds up polling for its own subscription. A
+// buffer lets the poller run ahead during a boot storm, at the cost of the
+// subscriber working through older batches before it catches up.
+//
+// When monitor_mode settles on signals, the changes come from the signal hub
+// instead, which never waits on a subscriber.
+func subscribe(conn systemdConn, set unitSet) (<-chan map[string]*systemd.UnitStatus, <-chan error) {
+	if hub != nil {
+		return hub.subscribe(C.Events.Buffer)
+	}
+	return conn.SubscribeUnitsCustom(C.Events.Interval, C.Events.Buffer,
+		func(u1, u2 *systemd.UnitStatus) bool { return *u1 != *u2 },
+		func(unit string) bool { return !set.Contains(unit) },
+	)
+}
+
+// apply shows a unit's state on its pixel, debounced when configured.
+func apply(service Service, pixel *led.Led, state string, sub string) {
+	if state == "activating" && pixel.CurrentStatus() == "stuck" {
+		state, sub = "stuck", "" // still the same attempt to start
+	}
+	look, ok := resolve(service, state, sub)
+	if !ok {
+		logr.Error("Unknown service statre", zap.String("event", state))
+	}
+	pixel.ApplyStateAfter(C.Debounce, state, look)
+	if state == "activating" && C.Strip.StuckAfter > 0 {
+		watchStuck(service, pixel)
+	}
+}
+
+// stuckTimers holds the one timer per pixel checking whether it is stuck, so
+// the activating events and resyncs of a slow start reset it instead of each
+// leaving another behind.
+var stuckTimers = struct {
+	sync.Mutex
+	timers map[*led.Led]*time.Timer
+}{timers: make(map[*led.Led]*time.Timer)}
+
+// watchStuck has markStuck check the pixel once it could have been activating
+// for stuck_after.
+func watchStuck(service Service, pixel *led.Led) {
+	wait := C.Debounce + C.Strip.StuckAfter
+	if pixel.CurrentStatus() == "activating" {
+		wait = C.Strip.StuckAfter - pixel.TimeInState()
+	}
+	stuckTimers.Lock()
+	defer stuckTimers.Unlock()
+	if timer, ok := stuckTimers.timers[pixel]; ok {
+		timer.Reset(wait)
+		return
+	}
+	stuckTimers.timers[pixel] = time.AfterFunc(wait, func() { markStuck(service, pixel) })
+}
+
+// markStuck shows a unit that has been activating for longer than stuck_after
+// as stuck, since it will most likely never get there.
+func markStuck(service Service, pixel *led.Led) {
+	if pixel.CurrentStatus() != "activating" {
+		return
+	}
+	if pixel.TimeInState() < C.Strip.StuckAfter {
+		watchStuck(service, pixel) // activating again since the timer was set
+		return
+	}
+	logr.Warn("Unit stuck activating", zap.String("name", pixel.Unit))
+	apply(service, pixel, "stuck", "")
+}
+
+// resync reads the unit's state straight from systemd and applies it, both to
+// show it as soon as the unit is added and so the pixel converges on the truth
+// when subscription updates may have gone astray.
+func resync(conn systemdConn, service Service, pixel *led.Led) {
+	properties, err := conn.GetUnitProperties(pixel.Unit)
+	if err != nil {
+		logr.Error("Failed to resync unit", zap.String("name", pixel.Unit), zap.Error(err))
+		return
+	}
+	alive()
+	load, _ := properties["LoadState"].(string)
+	state, _ := properties["ActiveState"].(string)
+	sub, _ := properties["SubState"].(string)
+	state, sub = effectiveState(load, state, sub)
+	if state == "failed" {
+		sub = failedResult(conn, pixel.Unit, sub)
+	}
+	apply(service, pixel, state, sub)
+}
+
+// failedResult returns why a failed unit failed, such as exit-code, timeout or
+// signal, for colours keyed like failed/timeout. When systemd can't say, sub
+// is returned.
+func failedResult(conn systemdConn, unit string, sub string) string {
+	kind := strings.TrimPrefix(path.Ext(unit), ".")
+	if kind == "" {
+		return sub
+	}
+	property, err := conn.GetUnitTypeProperty(unit, strings.ToUpper(kind[:1])+kind[1:], "Result")
+	if err != nil {
+		return sub
+	}
+	if result, ok := property.Value.Value().(string); ok && result != "" && result != "success" {
+		return result
+	}
+	return sub
+}
+
+// loadProblems are the load states shown in place of the active state, as a
+// masked or broken unit says more than that it is inactive.
+var loadProblems = map[string]bool{
+	"masked":      true,
+	"error":       true,
+	"bad-setting": true,
+}
+
+// effectiveState picks what to show for a unit: its load state when that is a
+// problem, otherwise its active and sub state.
+func effectiveState(load string, state string, sub string) (string, string) {
+	if loadProblems[load] {
+		return load, ""
+	}
+	return state, sub
+}
+
+// unitMissing reports whether systemd doesn't know the unit, or can't say.
+func unitMissing(conn systemdConn, unit string) bool {
+	loadstate, err := conn.GetUnitProperty(unit, "LoadState")
+	if err != nil {
+		logr.Error("Failed to get property:", zap.Error(err))
+		return true
+	}
+	if loadstate.Value == dbus.MakeVariant("not-found") {
+		logr.Info("Failed to find service", zap.String("name", unit))
+		return true
+	}
+	return false
+}
+
+// checkMissing applies the missing_units policy to a unit systemd may not
+// know, reporting whether it gets a pixel and, with the error policy, failing.
+func checkMissing(conn systemdConn, unit string, policy string) (bool, error) {
+	if !unitMissing(conn, unit) {
+		return true, nil
+	}
+	switch policy {
+	case "skip":
+		logr.Warn("Skipping missing unit", zap.String("name", unit))
+		return false, nil
+	case "error":
+		return false, fmt.Errorf("unit %s not found", unit)
+	default:
+		logr.Warn("Unit not found, waiting for it", zap.String("name", unit))
+		return true, nil
+	}
+}
+
+// addService shows the unit's state on its pixel until ctx is done.
+func addService(ctx context.Context, conn systemdConn, set unitSet, service Service, pixelRef *led.Led) {
+	subChannel, subErrors := subscribe(conn, set)
+	var svc = pixelRef.Unit
+	var activeSet = false
+	var invalid = false
+	var previous bool
+	for {
+		previous = invalid
+		invalid = unitMissing(conn, svc)
+
+		if invalid && !previous { // unit was removed or never existed
+			apply(service, pixelRef, "gone", "")
 		}
 
 		if invalid {
 			logr.Info("Waiting for service")
+			select {
+			case <-ctx.Done():
+				return
+			case <-time.After(time.Second):
+			}
 			if activeSet {
 				activeSet = false
 				set.Remove(svc) // no return value should ever occur
@@ -136,36 +704,34 @@ func addService(conn *systemd.Conn, set *systemd.SubscriptionSet, pixelRef *led.
 			if !activeSet {
 				activeSet = true
 				set.Add(svc) // no return value should ever occur
+				resync(conn, service, pixelRef)
+			}
+
+			var heartbeat <-chan time.Time // nil, so never ready, without a watchdog
+			if C.Watchdog.Timeout > 0 {
+				heartbeat = time.After(C.Watchdog.Timeout / 2)
 			}
 
 			select {
 			case event := <-subChannel:
-				if event[svc] != nil {
-					switch event[svc].ActiveState {
-					case "active":
-						pixelRef.SetColour(C.Strip.colours["active"])
-					case "inactive":
-						pixelRef.SetColour("44000005")
-						pixelRef.SetColour(C.Strip.colours["inactive"])
-					case "reloading":
-						pixelRef.SetColour("60606060")
-						pixelRef.SetColour(C.Strip.colours["reloading"])
-					case "failed":
-						pixelRef.SetColour("99000000")
-						pixelRef.SetColour(C.Strip.colours["failed"])
-					case "activating":
-						pixelRef.SetColour("00330010")
-						pixelRef.SetColour(C.Strip.colours["activating"])
-					case "deactivating":
-						pixelRef.SetColour("22000010")
-						pixelRef.SetColour(C.Strip.colours["deactivating"])
-					default:
-						logr.Error("Unknown service statre", zap.String("event", event[svc].ActiveState))
+				alive()
+				if unit := event[svc]; unit != nil {
+					state, sub := effectiveState(unit.LoadState, unit.ActiveState, unit.SubState)
+					if state == "failed" {
+						sub = failedResult(conn, svc, sub)
 					}
+					apply(service, pixelRef, state, sub)
 				}
 
 			case err := <-subErrors:
 				logr.Error("Unknown error, changes to systemd?", zap.Error(err))
+				resync(conn, service, pixelRef)
+
+			case <-heartbeat:
+				resync(conn, service, pixelRef)
+
+			case <-ctx.Done():
+				return
 			}
 		}
 	}
@@ -0,0 +1,423 @@
+package main
+
+import (
+	"errors"
+	"flag"
+	"os"
+	"path/filepath"
+	"reflect"
+	"strings"
+	"testing"
+	"time"
+
+	systemd "github.com/coreos/go-systemd/v22/dbus" // change namespace
+	"github.com/shift/systemd-status-leds/colour"
+	"github.com/shift/systemd-status-leds/led"
+
+	"github.com/jar-o/limlog"
+	"go.uber.org/zap"
+)
+
+func TestMain(m *testing.M) {
+	logr = limlog.NewLimlogZapWithConfig(limlog.NewZapConfigWithLevel(zap.ErrorLevel))
+	os.Exit(m.Run())
+}
+
+func TestMatchUnits(t *testing.T) {
+	units := []string{"docker-a.service", "sshd.service", "docker-b.service", "docker.socket", "getty@tty1.service"}
+	tests := []struct {
+		pattern string
+		want    []string
+	}{
+		{"docker-*.service", []string{"docker-a.service", "docker-b.service"}},
+		{"docker*", []string{"docker-a.service", "docker-b.service", "docker.socket"}},
+		{"*.socket", []string{"docker.socket"}},
+		{"getty@tty?.service", []string{"getty@tty1.service"}},
+		{"docker-[b].service", []string{"docker-b.service"}},
+		{"nginx-*.service", nil},
+		{"sshd.service", []string{"sshd.service"}},
+		{"[", nil}, // malformed, matches nothing
+	}
+	for _, tt := range tests {
+		if got := matchUnits(tt.pattern, units); !reflect.DeepEqual(got, tt.want) {
+			t.Errorf("matchUnits(%q) = %q, want %q", tt.pattern, got, tt.want)
+		}
+	}
+}
+
+// withConfig makes c the global config for the rest of the test.
+func withConfig(t *testing.T, c Config) {
+	t.Helper()
+	saved := C
+	C = c
+	t.Cleanup(func() { C = saved })
+}
+
+func TestResolveFallsBackOnBuiltInColours(t *testing.T) {
+	var c Config
+	c.Strip.Colours = map[string]string{"active": "00ff00", "failed": "ff0000"} // written before gone existed
+	withConfig(t, c)
+
+	for _, service := range []Service{{Unit: "a.service"}, {Unit: "b.service", Inherit: new(bool)}} {
+		look, ok := resolve(service, "gone", "")
+		want, _ := led.DefaultColour("gone")
+		if !ok || look.Colour != want {
+			t.Errorf("%s gone = %q, %t, want the built in %q", service.Unit, look.Colour, ok, want)
+		}
+	}
+}
+
+func TestResolveLoadProblems(t *testing.T) {
+	tests := []struct {
+		name    string
+		colours map[string]string
+		load    string
+		want    string
+	}{
+		{name: "configured masked", colours: map[string]string{"masked": "000033"}, load: "masked", want: "000033"},
+		{name: "built in masked", load: "masked", want: led.DefaultColours["masked"]},
+		{name: "built in error", load: "error", want: led.DefaultColours["error"]},
+		{name: "built in bad-setting", load: "bad-setting", want: led.DefaultColours["bad-setting"]},
+	}
+	for _, tt := range tests {
+		var c Config
+		c.Strip.Colours = tt.colours
+		withConfig(t, c)
+		state, sub := effectiveState(tt.load, "inactive", "dead")
+		look, ok := resolve(Service{Unit: "a.service"}, state, sub)
+		if !ok || look.Colour != tt.want {
+			t.Errorf("%s: %s unit = %q, %t, want %q", tt.name, tt.load, look.Colour, ok, tt.want)
+		}
+	}
+}
+
+func TestStuck(t *testing.T) {
+	var c Config
+	c.Strip.StuckAfter = 20 * time.Millisecond
+	withConfig(t, c)
+	service := Service{Unit: "a.service"}
+
+	stuck := &led.Led{Unit: "a.service"}
+	started := &led.Led{Unit: "b.service"}
+	for i := 0; i < 5; i++ { // activating events and resyncs of the same start
+		apply(service, stuck, "activating", "start")
+		apply(service, started, "activating", "start")
+	}
+	stuckTimers.Lock()
+	timers := len(stuckTimers.timers)
+	stuckTimers.Unlock()
+	if timers != 2 {
+		t.Errorf("%d stuck timers for 2 pixels, want one each", timers)
+	}
+	apply(service, started, "active", "running")
+
+	deadline := time.Now().Add(time.Second)
+	for stuck.CurrentStatus() != "stuck" {
+		if time.Now().After(deadline) {
+			t.Fatalf("status = %q after activating for a second, want stuck", stuck.CurrentStatus())
+		}
+		time.Sleep(5 * time.Millisecond)
+	}
+	if want, _ := led.DefaultColour("stuck"); stuck.CurrentColour() != want {
+		t.Errorf("stuck colour = %q, want the built in %q", stuck.CurrentColour(), want)
+	}
+	apply(service, stuck, "activating", "start")
+	if got := stuck.CurrentStatus(); got != "stuck" {
+		t.Errorf("status = %q after activating again, want still stuck", got)
+	}
+	if got := started.CurrentStatus(); got != "active" {
+		t.Errorf("status of a unit that started = %q, want active", got)
+	}
+}
+
+func TestConnectRetrying(t *testing.T) {
+	tests := []struct {
+		name     string
+		failures int
+		retry    time.Duration
+		err      bool
+		waits    []time.Duration
+	}{
+		{name: "first time", retry: time.Minute},
+		{name: "no retry", failures: 1, retry: 0, err: true},
+		{name: "doubling", failures: 3, retry: time.Minute, waits: []time.Duration{500 * time.Millisecond, time.Second, 2 * time.Second}},
+		{
+			name:     "capped",
+			failures: 8,
+			retry:    time.Minute,
+			waits:    []time.Duration{500 * time.Millisecond, time.Second, 2 * time.Second, 4 * time.Second, 8 * time.Second, 10 * time.Second, 10 * time.Second, 10 * time.Second},
+		},
+		{
+			name:     "deadline",
+			failures: 100,
+			retry:    30 * time.Second,
+			err:      true,
+			waits:    []time.Duration{500 * time.Millisecond, time.Second, 2 * time.Second, 4 * time.Second, 8 * time.Second, 10 * time.Second},
+		},
+	}
+	for _, tt := range tests {
+		clock := time.Date(2024, 1, 1, 0, 0, 0, 0, time.UTC)
+		var waits []time.Duration
+		attempts := 0
+		dial := func() (*systemd.Conn, error) {
+			if attempts++; attempts <= tt.failures {
+				return nil, errors.New("dbus not up yet")
+			}
+			return &systemd.Conn{}, nil
+		}
+		sleep := func(d time.Duration) {
+			waits = append(waits, d)
+			clock = clock.Add(d)
+		}
+		_, err := connectRetrying(dial, tt.retry, func() time.Time { return clock }, sleep)
+		if (err != nil) != tt.err {
+			t.Errorf("%s: err = %v, want an error: %t", tt.name, err, tt.err)
+		}
+		if !reflect.DeepEqual(waits, tt.waits) {
+			t.Errorf("%s: waited %v, want %v", tt.name, waits, tt.waits)
+		}
+	}
+}
+
+func TestCheckMissing(t *testing.T) {
+	tests := []struct {
+		policy string
+		failed bool // the lookup fails, so the unit counts as missing
+		add    bool
+		err    bool
+	}{
+		{policy: "warn", failed: true, add: true},
+		{policy: "skip", failed: true, add: false},
+		{policy: "error", failed: true, err: true},
+		{policy: "warn", add: true},
+		{policy: "skip", add: true},
+		{policy: "error", add: true},
+	}
+	for _, tt := range tests {
+		conn := newFakeSystemd()
+		conn.set("a.service", "active", "running", "")
+		if tt.failed {
+			conn.lookupErr = errors.New("dbus went away")
+		}
+		add, err := checkMissing(conn, "a.service", tt.policy)
+		if add != tt.add || (err != nil) != tt.err {
+			t.Errorf("checkMissing(%s) with a failing lookup %t = %t, %v, want %t, an error: %t", tt.policy, tt.failed, add, err, tt.add, tt.err)
+		}
+	}
+}
+
+func TestApplyFlags(t *testing.T) {
+	var c Config
+	c.Strip.Hertz = 30
+	c.Events.Buffer = 4
+
+	applyFlags(&c)
+	if c.Strip.Hertz != 30 || c.Events.Buffer != 4 {
+		t.Errorf("without flags hertz = %d, buffer = %d, want the config's 30 and 4", c.Strip.Hertz, c.Events.Buffer)
+	}
+
+	if err := flag.Set("frequency", "60"); err != nil {
+		t.Fatal(err)
+	}
+	defer flag.Set("frequency", "0")
+	applyFlags(&c)
+	if c.Strip.Hertz != 60 {
+		t.Errorf("with -frequency 60 hertz = %d, want 60", c.Strip.Hertz)
+	}
+	if c.Events.Buffer != 4 {
+		t.Errorf("buffer = %d, want the config's 4 as -event-buffer wasn't given", c.Events.Buffer)
+	}
+}
+
+func TestResolveOverlapping(t *testing.T) {
+	pulse := led.Pulse{MinScale: 0.2, MaxScale: 1, Period: time.Second}
+	var c Config
+	c.Strip.Colours = map[string]string{"active": "00ff00", "active/waiting": "002200", "failed": "ff0000", "failed/timeout": "ff8000"}
+	c.Strip.Pulses = map[string]led.Pulse{"failed": pulse}
+	c.Strip.MinHold = map[string]time.Duration{"failed": time.Minute}
+	own := Service{Unit: "own.service", States: map[string]string{"failed": "ff00ff"}}
+	plain := Service{Unit: "plain.service"}
+	tests := []struct {
+		name      string
+		service   Service
+		state     string
+		sub       string
+		problems  bool
+		colour    string
+		animation bool
+		hold      time.Duration
+	}{
+		{name: "sub state beats the state", service: plain, state: "active", sub: "waiting", colour: "002200"},
+		{name: "result beats failed", service: plain, state: "failed", sub: "timeout", colour: "ff8000", animation: true, hold: time.Minute},
+		{name: "states_map beats a more specific strip colour", service: own, state: "failed", sub: "timeout", colour: "ff00ff", animation: true, hold: time.Minute},
+		{name: "problems_only beats the colour", service: plain, state: "active", sub: "waiting", problems: true, colour: off.String()},
+		{name: "problems_only leaves failed lit", service: plain, state: "failed", sub: "exit-code", problems: true, colour: "ff0000", animation: true, hold: time.Minute},
+	}
+	for _, tt := range tests {
+		c.Strip.ProblemsOnly = tt.problems
+		withConfig(t, c)
+		look, _ := resolve(tt.service, tt.state, tt.sub)
+		if look.Colour != tt.colour || (look.Animation != nil) != tt.animation || look.Hold != tt.hold {
+			t.Errorf("%s: resolve(%s, %s/%s) = %s, animating %t, held %s, want %s, %t, %s", tt.name, tt.service.Unit, tt.state, tt.sub, look.Colour, look.Animation != nil, look.Hold, tt.colour, tt.animation, tt.hold)
+		}
+	}
+}
+
+func TestResolveUnknown(t *testing.T) {
+	withConfig(t, Config{})
+	look, ok := resolve(Service{Unit: "a.service"}, "nonsense", "")
+	if ok {
+		t.Error("resolve of a state nothing knows = ok")
+	}
+	if c, err := colour.Parse(look.Colour); err != nil || c == off {
+		t.Errorf("unknown state shows %q, want a visible colour", look.Colour)
+	}
+
+	var c Config
+	c.Strip.Colours = map[string]string{"unknown": "123456"}
+	withConfig(t, c)
+	if look, _ := resolve(Service{Unit: "a.service"}, "nonsense", ""); look.Colour != "123456" {
+		t.Errorf("unknown state shows %q, want the configured 123456", look.Colour)
+	}
+}
+
+func TestResolveInherit(t *testing.T) {
+	var c Config
+	c.Strip.Colours = map[string]string{"active": "00ff00", "failed": "ff0000"}
+	withConfig(t, c)
+	inherit, own := true, false
+	builtIn, _ := led.DefaultColour("failed")
+	tests := []struct {
+		inherit *bool
+		state   string
+		want    string
+	}{
+		{inherit: nil, state: "active", want: "0000ff"},
+		{inherit: nil, state: "failed", want: "ff0000"},
+		{inherit: &inherit, state: "failed", want: "ff0000"},
+		{inherit: &own, state: "active", want: "0000ff"},
+		{inherit: &own, state: "failed", want: builtIn},
+	}
+	for _, tt := range tests {
+		service := Service{Unit: "a.service", States: map[string]string{"active": "0000ff"}, Inherit: tt.inherit}
+		if look, _ := resolve(service, tt.state, ""); look.Colour != tt.want {
+			t.Errorf("%s with inherit %t = %q, want %q", tt.state, service.Inherits(), look.Colour, tt.want)
+		}
+	}
+}
+
+func TestResolveInactiveOff(t *testing.T) {
+	var c Config
+	c.Strip.Colours = map[string]string{"inactive": "01010101"}
+	c.Strip.InactiveOff = true
+	withConfig(t, c)
+	look, ok := resolve(Service{Unit: "a.service"}, "inactive", "dead")
+	if parsed, err := colour.Parse(look.Colour); !ok || err != nil || parsed != (colour.Colour{}) {
+		t.Errorf("inactive with inactive_off = %q, %t, want all zero", look.Colour, ok)
+	}
+	if look.Animation != nil {
+		t.Error("inactive with inactive_off animates")
+	}
+}
+
+func TestResolveProblemsOnly(t *testing.T) {
+	var c Config
+	c.Strip.Colours = map[string]string{"active": "00ff00", "inactive": "010101", "failed": "ff0000", "activating": "004422"}
+	c.Strip.ProblemsOnly = true
+	withConfig(t, c)
+	tests := []struct {
+		state string
+		want  string
+	}{
+		{state: "active", want: off.String()},
+		{state: "inactive", want: off.String()},
+		{state: "activating", want: "004422"},
+		{state: "failed", want: "ff0000"},
+	}
+	for _, tt := range tests {
+		if look, _ := resolve(Service{Unit: "a.service"}, tt.state, ""); look.Colour != tt.want {
+			t.Errorf("%s with problems_only = %q, want %q", tt.state, look.Colour, tt.want)
+		}
+	}
+}
+
+func TestResolveDeactivatingFade(t *testing.T) {
+	var c Config
+	c.Strip.Colours = map[string]string{"active": "00ff00", "deactivating": "224400"}
+	c.Strip.DeactivateFade = time.Second
+	withConfig(t, c)
+	look, _ := resolve(Service{Unit: "a.service"}, "deactivating", "stop")
+	if look.Animation == nil {
+		t.Fatal("deactivating with deactivating_fade doesn't animate")
+	}
+	base, _ := colour.Parse(look.Colour)
+	last := 2.0
+	for elapsed := time.Duration(0); elapsed <= time.Second; elapsed += 250 * time.Millisecond {
+		frame := look.Animation.Apply(base, elapsed)
+		if l := frame.Luminance(); l >= last {
+			t.Errorf("frame %s in = %v, no dimmer than the one before", elapsed, frame)
+		} else {
+			last = l
+		}
+	}
+	if end := look.Animation.Apply(base, time.Second); end != off {
+		t.Errorf("faded to %v, want off", end)
+	}
+
+	c.Strip.DisableAnimations = true
+	withConfig(t, c)
+	if look, _ := resolve(Service{Unit: "a.service"}, "deactivating", "stop"); look.Colour != "224400" {
+		t.Errorf("deactivating without animations = %q, want its own 224400", look.Colour)
+	}
+}
+
+func TestSetLogFormat(t *testing.T) {
+	tests := []struct {
+		format   string
+		encoding string
+		err      bool
+	}{
+		{format: "", encoding: "json"},
+		{format: "text", encoding: "console"},
+		{format: "json", encoding: "json"},
+		{format: "xml", encoding: "json", err: true},
+	}
+	for _, tt := range tests {
+		cfg := zap.Config{Encoding: "json", Level: zap.NewAtomicLevelAt(zap.InfoLevel)}
+		err := setLogFormat(&cfg, tt.format)
+		if (err != nil) != tt.err || cfg.Encoding != tt.encoding {
+			t.Errorf("setLogFormat(%q) = %v with encoding %q, want %q, an error: %t", tt.format, err, cfg.Encoding, tt.encoding, tt.err)
+		}
+		if cfg.Level.Level() != zap.InfoLevel {
+			t.Errorf("setLogFormat(%q) changed the level to %s", tt.format, cfg.Level.Level())
+		}
+	}
+}
+
+func TestConnectInvalidAddress(t *testing.T) {
+	for _, address := range []string{"not an address", "nonsense:foo=bar"} {
+		conn, err := connect(address)
+		if err == nil || conn != nil {
+			t.Errorf("connect(%q) = %v, %v, want an error", address, conn, err)
+			continue
+		}
+		if !strings.Contains(err.Error(), address) {
+			t.Errorf("connect(%q) error %q doesn't name the address", address, err)
+		}
+	}
+}
+
+func TestConnectAddress(t *testing.T) {
+	bus := filepath.Join(t.TempDir(), "bus")
+	address := "unix:path=" + bus
+	_, err := connect(address)
+	if err == nil {
+		t.Fatalf("connect(%q) to no bus succeeded", address)
+	}
+	if !strings.Contains(err.Error(), "dbus "+address) {
+		t.Errorf("connect error %q doesn't name the address", err)
+	}
+	if !strings.Contains(err.Error(), "dial unix "+bus) {
+		t.Errorf("connect error %q doesn't come from dialling %s", err, bus)
+	}
+}
@@ -0,0 +1,194 @@
+package main
+
+import (
+	"errors"
+	"sync"
+	"time"
+
+	systemd "github.com/coreos/go-systemd/v22/dbus" // change namespace
+	"go.uber.org/zap"
+)
+
+// monitor is how unit changes reach the LEDs.
+type monitor int
+
+const (
+	pollUnits    monitor = iota // go-systemd polls each subscription
+	signalUnits                 // the signal hub, fed by D-Bus signals
+	signalOrPoll                // the signal hub, polling until a signal arrives
+)
+
+// chooseMonitor settles monitor_mode, calling subscribe to ask systemd for its
+// signals unless polling was asked for. With auto, signals that can't be had
+// fall back on polling, and so do signals that subscribing promised but that
+// never turn up, as in a container where they aren't delivered.
+func chooseMonitor(mode string, subscribe func() error) (monitor, error) {
+	if mode == "poll" {
+		return pollUnits, nil
+	}
+	err := subscribe()
+	switch {
+	case err == nil && mode == "auto":
+		return signalOrPoll, nil
+	case err == nil:
+		return signalUnits, nil
+	case mode == "auto":
+		logr.Warn("systemd signals unavailable, polling instead", zap.Error(err))
+		return pollUnits, nil
+	default:
+		return pollUnits, err
+	}
+}
+
+// signalHub stands in for go-systemd's polling when units are watched through
+// D-Bus signals. systemd only reports the new sub state of a unit, so the hub
+// reads the rest and hands the change to every subscriber, in the same shape
+// SubscribeUnitsCustom would. Signals come for every unit, so those outside
+// set are dropped before costing a D-Bus call, and a unit is only reported
+// when its state differs from the last one sent.
+type signalHub struct {
+	sync.Mutex
+	set     unitSet
+	last    map[string]systemd.UnitStatus // as last sent
+	updates []chan map[string]*systemd.UnitStatus
+	errors  []chan error
+
+	heard     chan struct{} // closed once the first signal arrives
+	heardOnce sync.Once
+}
+
+// hub is set when monitor_mode has settled on signals.
+var hub *signalHub
+
+var errDropped = errors.New("unit changes dropped for a slow subscriber")
+
+func newSignalHub(set unitSet) *signalHub {
+	return &signalHub{set: set, last: make(map[string]systemd.UnitStatus), heard: make(chan struct{})}
+}
+
+// startSignals has systemd's PropertiesChanged signals feed a new hub, which
+// with poll also polls the units in set until the first of them arrives.
+func startSignals(conn *systemd.Conn, set unitSet, poll bool) *signalHub {
+	h := newSignalHub(set)
+	updates := make(chan *systemd.SubStateUpdate, 256)
+	errs := make(chan error, 16)
+	conn.SetSubStateSubscriber(updates, errs)
+	go h.run(conn, updates, errs)
+	if poll {
+		go h.poll(conn, C.Events.Interval)
+	}
+	return h
+}
+
+// subscribe adds a subscriber, whose channels hold up to buffer changes.
+func (h *signalHub) subscribe(buffer int) (<-chan map[string]*systemd.UnitStatus, <-chan error) {
+	updates := make(chan map[string]*systemd.UnitStatus, buffer+1)
+	errs := make(chan error, 1)
+	h.Lock()
+	defer h.Unlock()
+	h.updates = append(h.updates, updates)
+	h.errors = append(h.errors, errs)
+	return updates, errs
+}
+
+func (h *signalHub) run(conn systemdConn, updates <-chan *systemd.SubStateUpdate, errs <-chan error) {
+	for {
+		select {
+		case update := <-updates:
+			h.heardOnce.Do(func() { close(h.heard) })
+			if !h.set.Contains(update.UnitName) {
+				continue
+			}
+			properties, err := conn.GetUnitProperties(update.UnitName)
+			if err != nil {
+				h.fail(err)
+				continue
+			}
+			status := systemd.UnitStatus{Name: update.UnitName, SubState: update.SubState}
+			status.LoadState, _ = properties["LoadState"].(string)
+			status.ActiveState, _ = properties["ActiveState"].(string)
+			h.report(status)
+
+		case err := <-errs:
+			logr.Error("systemd signal failed", zap.Error(err))
+			h.fail(err)
+		}
+	}
+}
+
+// poll reads the units every interval until the first signal arrives.
+func (h *signalHub) poll(conn systemdConn, interval time.Duration) {
+	for {
+		select {
+		case <-h.heard:
+			return
+		case <-time.After(interval):
+			h.pollOnce(conn)
+		}
+	}
+}
+
+// pollOnce reports the state of every unit in the set, and that those systemd
+// no longer lists have gone.
+func (h *signalHub) pollOnce(conn systemdConn) {
+	units, err := conn.ListUnits()
+	if err != nil {
+		h.fail(err)
+		return
+	}
+	listed := make(map[string]bool, len(units))
+	for _, unit := range units {
+		if !h.set.Contains(unit.Name) {
+			continue
+		}
+		listed[unit.Name] = true
+		h.report(systemd.UnitStatus{Name: unit.Name, LoadState: unit.LoadState, ActiveState: unit.ActiveState, SubState: unit.SubState})
+	}
+	h.Lock()
+	defer h.Unlock()
+	for name := range h.last {
+		if !listed[name] && h.set.Contains(name) {
+			delete(h.last, name)
+			h.send(map[string]*systemd.UnitStatus{name: nil})
+		}
+	}
+}
+
+// report sends the unit's status unless it is the one sent last.
+func (h *signalHub) report(status systemd.UnitStatus) {
+	h.Lock()
+	defer h.Unlock()
+	if last, ok := h.last[status.Name]; ok && last == status {
+		return
+	}
+	h.last[status.Name] = status
+	h.send(map[string]*systemd.UnitStatus{status.Name: &status})
+}
+
+// send hands the change to every subscriber without waiting on any. One that
+// has fallen behind gets an error instead, which makes it resync. The caller
+// holds the lock.
+func (h *signalHub) send(change map[string]*systemd.UnitStatus) {
+	for i, updates := range h.updates {
+		select {
+		case updates <- change:
+		default:
+			select {
+			case h.errors[i] <- errDropped:
+			default: // already due to resync
+			}
+		}
+	}
+}
+
+// fail passes err to every subscriber that hasn't one waiting already.
+func (h *signalHub) fail(err error) {
+	h.Lock()
+	defer h.Unlock()
+	for _, errs := range h.errors {
+		select {
+		case errs <- err:
+		default:
+		}
+	}
+}
@@ -0,0 +1,179 @@
+package main
+
+import (
+	"errors"
+	"fmt"
+	"sync"
+	"testing"
+	"time"
+
+	systemd "github.com/coreos/go-systemd/v22/dbus" // change namespace
+)
+
+func TestChooseMonitor(t *testing.T) {
+	unavailable := errors.New("no signals here")
+	tests := []struct {
+		mode      string
+		err       error
+		want      monitor
+		fails     bool
+		subscribe bool
+	}{
+		{mode: "poll", want: pollUnits},
+		{mode: "poll", err: unavailable, want: pollUnits},
+		{mode: "signal", want: signalUnits, subscribe: true},
+		{mode: "signal", err: unavailable, fails: true, subscribe: true},
+		{mode: "auto", want: signalOrPoll, subscribe: true},
+		{mode: "auto", err: unavailable, want: pollUnits, subscribe: true},
+	}
+	for _, tt := range tests {
+		subscribed := false
+		got, err := chooseMonitor(tt.mode, func() error {
+			subscribed = true
+			return tt.err
+		})
+		if (err != nil) != tt.fails || (err == nil && got != tt.want) {
+			t.Errorf("chooseMonitor(%q) with subscribe returning %v = %d, %v, want %d, failing %t", tt.mode, tt.err, got, err, tt.want, tt.fails)
+		}
+		if subscribed != tt.subscribe {
+			t.Errorf("chooseMonitor(%q) subscribed %t, want %t", tt.mode, subscribed, tt.subscribe)
+		}
+	}
+}
+
+// countingSystemd counts the properties read for each unit.
+type countingSystemd struct {
+	*fakeSystemd
+	mu    sync.Mutex
+	reads map[string]int
+}
+
+func (c *countingSystemd) GetUnitProperties(unit string) (map[string]interface{}, error) {
+	c.mu.Lock()
+	c.reads[unit]++
+	c.mu.Unlock()
+	return c.fakeSystemd.GetUnitProperties(unit)
+}
+
+func (c *countingSystemd) read(unit string) int {
+	c.mu.Lock()
+	defer c.mu.Unlock()
+	return c.reads[unit]
+}
+
+// next waits a little for a change, returning nil if none comes.
+func next(updates <-chan map[string]*systemd.UnitStatus) map[string]*systemd.UnitStatus {
+	select {
+	case change := <-updates:
+		return change
+	case <-time.After(100 * time.Millisecond):
+		return nil
+	}
+}
+
+func TestSignalHubFilters(t *testing.T) {
+	conn := &countingSystemd{fakeSystemd: newFakeSystemd(), reads: make(map[string]int)}
+	conn.set("a.service", "active", "running", "")
+	conn.set("other.service", "active", "running", "")
+	h := newSignalHub(&fakeSet{units: map[string]bool{"a.service": true}})
+	changes, _ := h.subscribe(4)
+	signals := make(chan *systemd.SubStateUpdate)
+	go h.run(conn, signals, make(chan error))
+
+	signals <- &systemd.SubStateUpdate{UnitName: "other.service", SubState: "running"}
+	signals <- &systemd.SubStateUpdate{UnitName: "a.service", SubState: "running"}
+	change := next(changes)
+	if status := change["a.service"]; len(change) != 1 || status == nil || status.ActiveState != "active" {
+		t.Fatalf("first change = %v, want a.service active", change)
+	}
+	if n := conn.read("other.service"); n != 0 {
+		t.Errorf("read other.service %d times, want none as it isn't shown", n)
+	}
+
+	signals <- &systemd.SubStateUpdate{UnitName: "a.service", SubState: "running"}
+	if change := next(changes); change != nil {
+		t.Errorf("repeated signal sent %v, want nothing", change)
+	}
+}
+
+func TestSignalHubPollsUntilSignalled(t *testing.T) {
+	conn := newFakeSystemd()
+	conn.set("a.service", "active", "running", "")
+	h := newSignalHub(&fakeSet{units: map[string]bool{"a.service": true}})
+	changes, _ := h.subscribe(4)
+	signals := make(chan *systemd.SubStateUpdate)
+	go h.run(conn, signals, make(chan error))
+	go h.poll(conn, 5*time.Millisecond)
+
+	if change := next(changes); change["a.service"] == nil {
+		t.Fatalf("polled change = %v, want a.service while no signal has come", change)
+	}
+	signals <- &systemd.SubStateUpdate{UnitName: "a.service", SubState: "running"}
+	time.Sleep(20 * time.Millisecond) // for the poller to notice
+
+	// No signal comes for this, so only a poll would see it.
+	conn.set("a.service", "inactive", "dead", "")
+	if change := next(changes); change != nil {
+		t.Errorf("still polling after a signal, sent %v", change)
+	}
+}
+
+func TestSignalHubDeduplicates(t *testing.T) {
+	conn := newFakeSystemd()
+	conn.set("a.service", "active", "running", "")
+	h := newSignalHub(&fakeSet{units: map[string]bool{"a.service": true}})
+	changes, _ := h.subscribe(4)
+
+	h.pollOnce(conn)
+	h.pollOnce(conn)
+	if len(changes) != 1 {
+		t.Fatalf("two identical polls sent %d changes, want 1", len(changes))
+	}
+	<-changes
+	conn.set("a.service", "failed", "failed", "exit-code")
+	h.pollOnce(conn)
+	if change := next(changes); change["a.service"] == nil || change["a.service"].ActiveState != "failed" {
+		t.Errorf("change after failing = %v, want a.service failed", change)
+	}
+	conn.remove("a.service")
+	h.pollOnce(conn)
+	if change := next(changes); change == nil || change["a.service"] != nil {
+		t.Errorf("change after removal = %v, want a.service gone", change)
+	}
+}
+
+func TestSignalHubSlowSubscriber(t *testing.T) {
+	h := newSignalHub(&fakeSet{units: map[string]bool{"a.service": true}})
+	fast, fastErrors := h.subscribe(0)
+	_, slowErrors := h.subscribe(0) // never read
+
+	for i := 0; i < 10; i++ {
+		done := make(chan struct{})
+		go func(i int) {
+			h.report(systemd.UnitStatus{Name: "a.service", ActiveState: "active", SubState: fmt.Sprint(i)})
+			close(done)
+		}(i)
+		select {
+		case <-done:
+		case <-time.After(time.Second):
+			t.Fatalf("event %d blocked on the slow subscriber", i)
+		}
+		change := next(fast)
+		if status := change["a.service"]; status == nil || status.SubState != fmt.Sprint(i) {
+			t.Fatalf("fast subscriber got %v for event %d", change, i)
+		}
+	}
+	select {
+	case err := <-fastErrors:
+		t.Errorf("fast subscriber got %v", err)
+	default:
+	}
+	select {
+	case err := <-slowErrors:
+		if !errors.Is(err, errDropped) {
+			t.Errorf("slow subscriber got %v, want errDropped", err)
+		}
+	default:
+		t.Error("slow subscriber wasn't told it dropped events")
+	}
+}
@@ -0,0 +1,106 @@
+package status
+
+import (
+	"encoding/json"
+	"errors"
+	"fmt"
+	"io/fs"
+	"net"
+	"os"
+
+	"github.com/shift/systemd-status-leds/colour"
+	"github.com/shift/systemd-status-leds/led"
+	"github.com/shift/systemd-status-leds/strip"
+)
+
+// Snapshot is the layout of the strip and the state of every service pixel.
+type Snapshot struct {
+	Length    int     `json:"length"`
+	Leds      []Entry `json:"leds"`
+	FrameTime float64 `json:"frame_time"` // seconds
+	FrameRate float64 `json:"frame_rate"` // frames per second
+}
+
+type Entry struct {
+	Position  int     `json:"position"`
+	Unit      string  `json:"unit"`
+	Label     string  `json:"label"` // the unit if no label is configured
+	State     string  `json:"state"`
+	Previous  string  `json:"previous_state,omitempty"`
+	Colour    string  `json:"color"`
+	Text      string  `json:"text_color"`    // black or white, to read over color
+	InState   float64 `json:"time_in_state"` // seconds
+	Animating bool    `json:"animating"`
+}
+
+// Take captures a snapshot of the strip.
+func Take(s *strip.Strip) Snapshot {
+	pixels := s.Leds()
+	frameTime, frameRate := s.FrameStats()
+	snapshot := Snapshot{Length: s.Config.Length, Leds: make([]Entry, 0, len(pixels)), FrameTime: frameTime.Seconds(), FrameRate: frameRate}
+	for _, p := range pixels {
+		snapshot.Leds = append(snapshot.Leds, entry(s, p))
+	}
+	return snapshot
+}
+
+func entry(s *strip.Strip, p *led.Led) Entry {
+	inState := p.TimeInState().Seconds()
+	animating := !s.Config.DisableAnimations && p.IsAnimating()
+	previous := p.PreviousStatus()
+	p.RLock()
+	e := Entry{Position: s.Config.Position(p.Number - 1), Unit: p.Unit, Label: p.Label, State: p.Status, Previous: previous, Colour: p.Colour, InState: inState, Animating: animating}
+	p.RUnlock()
+	if e.Label == "" {
+		e.Label = e.Unit
+	}
+	if c, err := colour.Parse(e.Colour); err == nil {
+		e.Colour = c.String() // short forms expanded for clients
+		e.Text = c.ReadableText().String()
+	}
+	return e
+}
+
+// Serve listens on a Unix socket at path and writes a snapshot of the strip to
+// every client that connects, closing the connection straight after.
+func Serve(path string, s *strip.Strip) error {
+	if err := removeStale(path); err != nil {
+		return err
+	}
+	listener, err := net.Listen("unix", path)
+	if err != nil {
+		return err
+	}
+	defer listener.Close()
+
+	for {
+		conn, err := listener.Accept()
+		if err != nil {
+			return err
+		}
+		go func(conn net.Conn) {
+			defer conn.Close()
+			buf, err := json.Marshal(Take(s))
+			if err != nil {
+				return
+			}
+			_, _ = conn.Write(buf)
+		}(conn)
+	}
+}
+
+// removeStale removes a socket left behind by a previous run. Anything else at
+// path is left alone, since the daemon usually runs as root.
+func removeStale(path string) error {
+	info, err := os.Lstat(path)
+	if errors.Is(err, fs.ErrNotExist) {
+		return nil
+	}
+	if err != nil {
+		return err
+	}
+	if info.Mode()&os.ModeSocket == 0 {
+		return fmt.Errorf("%s exists and isn't a socket", path)
+	}
+	return os.Remove(path)
+}
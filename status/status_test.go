@@ -0,0 +1,125 @@
+package status
+
+import (
+	"encoding/json"
+	"io"
+	"net/http"
+	"net/http/httptest"
+	"testing"
+
+	"github.com/shift/systemd-status-leds/led"
+	"github.com/shift/systemd-status-leds/strip"
+	"periph.io/x/conn/v3/physic"
+)
+
+// testStrip is a strip of 4 with web.service, labelled Web, failed and
+// db.service active.
+func testStrip(t *testing.T) *strip.Strip {
+	t.Helper()
+	c := &strip.Config{
+		Length:     4,
+		Channels:   3,
+		Hertz:      10,
+		SpiSpeedHz: int(strip.DefaultSpiSpeed / physic.Hertz),
+		SpiMode:    strip.DefaultSpiMode,
+		SpiBits:    strip.DefaultSpiBits,
+	}
+	c.Loading.Colour = strip.DefaultLoadingColour
+	c.Loading.Style = "solid"
+	s, err := strip.New(nil, c, io.Discard)
+	if err != nil {
+		t.Fatalf("strip.New: %v", err)
+	}
+	web, err := s.Add("web.service")
+	if err != nil {
+		t.Fatalf("Add: %v", err)
+	}
+	web.SetLabel("Web")
+	web.ApplyState("failed", led.Look{Colour: "f00"})
+	db, err := s.Add("db.service")
+	if err != nil {
+		t.Fatalf("Add: %v", err)
+	}
+	db.ApplyState("active", led.Look{Colour: "00ff00"})
+	return s
+}
+
+func TestSnapshotJSON(t *testing.T) {
+	buf, err := json.Marshal(Take(testStrip(t)))
+	if err != nil {
+		t.Fatalf("Marshal: %v", err)
+	}
+	var got map[string]interface{}
+	if err := json.Unmarshal(buf, &got); err != nil {
+		t.Fatalf("Unmarshal: %v", err)
+	}
+	for _, key := range []string{"length", "leds", "frame_time", "frame_rate"} {
+		if _, ok := got[key]; !ok {
+			t.Errorf("snapshot %s has no %q", buf, key)
+		}
+	}
+	leds, _ := got["leds"].([]interface{})
+	if len(leds) != 2 {
+		t.Fatalf("snapshot %s has %d leds, want 2", buf, len(leds))
+	}
+	web, _ := leds[0].(map[string]interface{})
+	for _, key := range []string{"position", "unit", "label", "state", "color", "text_color", "time_in_state", "animating"} {
+		if _, ok := web[key]; !ok {
+			t.Errorf("led %v has no %q", web, key)
+		}
+	}
+	if web["color"] != "ff000000" || web["state"] != "failed" {
+		t.Errorf("led = %v, want failed in ff000000, the short form expanded", web)
+	}
+}
+
+func TestTake(t *testing.T) {
+	snapshot := Take(testStrip(t))
+	if snapshot.Length != 4 || len(snapshot.Leds) != 2 {
+		t.Fatalf("snapshot of length %d with %d leds, want 4 and 2", snapshot.Length, len(snapshot.Leds))
+	}
+	want := []struct {
+		position int
+		unit     string
+		state    string
+	}{
+		{0, "web.service", "failed"},
+		{1, "db.service", "active"},
+	}
+	for i, w := range want {
+		e := snapshot.Leds[i]
+		if e.Position != w.position || e.Unit != w.unit || e.State != w.state {
+			t.Errorf("led %d = %s %s at %d, want %s %s at %d", i, e.Unit, e.State, e.Position, w.unit, w.state, w.position)
+		}
+	}
+}
+
+func TestTakeLabel(t *testing.T) {
+	snapshot := Take(testStrip(t))
+	if got := snapshot.Leds[0].Label; got != "Web" {
+		t.Errorf("label = %q, want the configured Web", got)
+	}
+	if got := snapshot.Leds[1].Label; got != "db.service" {
+		t.Errorf("label = %q, want the unit db.service as none is configured", got)
+	}
+}
+
+func TestHandlerStatus(t *testing.T) {
+	server := httptest.NewServer(Handler(testStrip(t)))
+	defer server.Close()
+	resp, err := http.Get(server.URL + "/api/status")
+	if err != nil {
+		t.Fatalf("GET /api/status: %v", err)
+	}
+	defer resp.Body.Close()
+	if resp.StatusCode != http.StatusOK || resp.Header.Get("Content-Type") != "application/json" {
+		t.Errorf("GET /api/status = %s, %q, want 200 OK with JSON", resp.Status, resp.Header.Get("Content-Type"))
+	}
+	var snapshot Snapshot
+	if err := json.NewDecoder(resp.Body).Decode(&snapshot); err != nil {
+		t.Fatalf("decoding /api/status: %v", err)
+	}
+	if snapshot.Length != 4 || len(snapshot.Leds) != 2 || snapshot.Leds[0].Unit != "web.service" {
+		t.Errorf("/api/status = %+v, want the two LEDs of a strip of 4", snapshot)
+	}
+}
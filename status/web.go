@@ -0,0 +1,88 @@
+package status
+
+import (
+	"encoding/json"
+	"io"
+	"net/http"
+
+	"github.com/shift/systemd-status-leds/strip"
+)
+
+// Handler serves a dashboard page at / which polls the JSON snapshot served
+// at /api/status.
+func Handler(s *strip.Strip) http.Handler {
+	mux := http.NewServeMux()
+	mux.HandleFunc("/api/status", func(w http.ResponseWriter, r *http.Request) {
+		buf, err := json.Marshal(Take(s))
+		if err != nil {
+			http.Error(w, err.Error(), http.StatusInternalServerError)
+			return
+		}
+		w.Header().Set("Content-Type", "application/json")
+		_, _ = w.Write(buf)
+	})
+	mux.HandleFunc("/", func(w http.ResponseWriter, r *http.Request) {
+		if r.URL.Path != "/" {
+			http.NotFound(w, r)
+			return
+		}
+		w.Header().Set("Content-Type", "text/html; charset=utf-8")
+		_, _ = io.WriteString(w, page)
+	})
+	return mux
+}
+
+const page = `<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>systemd status leds</title>
+<style>
+body { font-family: sans-serif; }
+td, th { padding: 0.2em 0.8em; text-align: left; }
+.swatch { width: 2em; height: 1em; border: 1px solid #888; }
+</style>
+</head>
+<body>
+<table id="leds">
+<tr><th></th><th>Unit</th><th>State</th><th>For</th></tr>
+</table>
+<p id="frames"></p>
+<script>
+function since(seconds) {
+	if (seconds < 60) return Math.floor(seconds) + "s";
+	if (seconds < 3600) return Math.floor(seconds / 60) + "m";
+	return Math.floor(seconds / 3600) + "h";
+}
+
+async function refresh() {
+	const snapshot = await (await fetch("api/status")).json();
+	const table = document.getElementById("leds");
+	while (table.rows.length > 1) table.deleteRow(1);
+	for (const led of snapshot.leds) {
+		const row = table.insertRow();
+		const swatch = document.createElement("div");
+		swatch.className = "swatch";
+		swatch.style.background = "#" + led.color.slice(0, 6);
+		if (led.animating) swatch.title = "animating";
+		row.insertCell().appendChild(swatch);
+		row.insertCell().textContent = led.label;
+		row.lastChild.title = led.unit;
+		const state = row.insertCell();
+		state.textContent = led.state;
+		if (led.text_color) {
+			state.style.background = "#" + led.color.slice(0, 6);
+			state.style.color = "#" + led.text_color.slice(0, 6);
+		}
+		row.insertCell().textContent = since(led.time_in_state);
+	}
+	document.getElementById("frames").textContent =
+		snapshot.frame_rate.toFixed(1) + " frames/s, " + (snapshot.frame_time * 1000).toFixed(1) + " ms per frame";
+}
+
+refresh();
+setInterval(refresh, 2000);
+</script>
+</body>
+</html>
+`
@@ -2,94 +2,865 @@ package strip
 
 import (
 	"bytes"
+	"context"
+	"encoding/hex"
 	"errors"
+	"fmt"
 	"github.com/jar-o/limlog"
+	"github.com/shift/systemd-status-leds/colour"
 	"github.com/shift/systemd-status-leds/led"
+	"go.uber.org/zap"
+	"io"
+	"math"
 	"periph.io/x/conn/v3/physic"
 	"periph.io/x/conn/v3/spi"
 	"periph.io/x/conn/v3/spi/spireg"
 	"periph.io/x/devices/v3/nrzled"
 	"periph.io/x/host/v3"
-	"strconv"
+	"strings"
+	"sync"
 	"time"
 )
 
 var (
-	Loading = []byte{60, 60, 60, 60}
+	ErrFull        = errors.New("Already at one service per pixel.")
+	ErrUnknownUnit = errors.New("No pixel for unit")
+	ErrClosed      = errors.New("Strip is closed")
 )
 
+// ConfigError is returned for a strip configuration that can't work.
+type ConfigError struct {
+	Err error
+}
+
+func (e *ConfigError) Error() string { return e.Err.Error() }
+func (e *ConfigError) Unwrap() error { return e.Err }
+
+// DeviceError is returned when the SPI device can't be set up.
+type DeviceError struct {
+	Err error
+}
+
+func (e *DeviceError) Error() string { return "spi: " + e.Err.Error() }
+func (e *DeviceError) Unwrap() error { return e.Err }
+
+const (
+	DefaultLoadingColour = "3c3c3c3c"
+
+	// How long the chasing loading pixel stays on each position.
+	chaseStep = 100 * time.Millisecond
+)
+
+// The SPI clock drives the WS281x bit timing, so only a narrow band works.
+const (
+	DefaultSpiSpeed = 2500 * physic.KiloHertz
+	MinSpiSpeed     = 400 * physic.KiloHertz
+	MaxSpiSpeed     = 10 * physic.MegaHertz
+)
+
+// nrzled asks for mode 3 with 8 bit words, some controllers need otherwise.
+const (
+	DefaultSpiMode = int(spi.Mode3)
+	DefaultSpiBits = 8
+)
+
+// Config is the strip section of the configuration file. Hertz is how often
+// the frame is refreshed, SpiSpeedHz the clock the frame is sent at, and
+// SpiMode and SpiBits set up the controller in place of the nrzled defaults.
+type Config struct {
+	Length            int
+	Channels          int
+	Hertz             int
+	SpiSpeedHz        int `mapstructure:"spi_speed_hz" yaml:"spi_speed_hz"`
+	SpiMode           int `mapstructure:"spi_mode" yaml:"spi_mode"`
+	SpiBits           int `mapstructure:"spi_bits" yaml:"spi_bits"`
+	Spidev            string
+	Colours           map[string]string
+	Reverse           bool
+	StartOffset       int `mapstructure:"start_offset" yaml:"start_offset"`
+	Pulses            map[string]led.Pulse
+	Gamma             float64
+	GammaWhite        float64 `mapstructure:"gamma_white" yaml:"gamma_white"`
+	DisableAnimations bool    `mapstructure:"disable_animations" yaml:"disable_animations,omitempty"`
+	Loading           struct {
+		Colour string
+		Style  string // solid or chase
+	}
+	// Gains for red, green and blue, e.g. to warm up a cool white.
+	WhiteBalance   []float64                `mapstructure:"white_balance" yaml:"white_balance,omitempty"`
+	MinHold        map[string]time.Duration `mapstructure:"min_hold" yaml:"min_hold,omitempty"`
+	ActivatingRamp time.Duration            `mapstructure:"activating_ramp" yaml:"activating_ramp,omitempty"`
+	DeactivateFade time.Duration            `mapstructure:"deactivating_fade" yaml:"deactivating_fade,omitempty"`
+	// How long a unit may be activating before it is shown as stuck instead.
+	StuckAfter     time.Duration            `mapstructure:"stuck_after" yaml:"stuck_after,omitempty"`
+	// Caps on the sum of the channel values sent, per LED and for the whole
+	// strip, to keep within what the power supply can deliver. Zero is no cap.
+	MaxPixelSum    int                      `mapstructure:"max_pixel_sum" yaml:"max_pixel_sum,omitempty"`
+	MaxStripSum    int                      `mapstructure:"max_strip_sum" yaml:"max_strip_sum,omitempty"`
+	InactiveOff    bool                     `mapstructure:"inactive_off" yaml:"inactive_off,omitempty"`
+	ProblemsOnly   bool                     `mapstructure:"problems_only" yaml:"problems_only,omitempty"`
+	ResetBytes     int                      `mapstructure:"reset_bytes" yaml:"reset_bytes,omitempty"`
+	// Fraction of full brightness nothing may exceed, applied last. Zero is none.
+	MaxBrightness  float64                  `mapstructure:"max_brightness" yaml:"max_brightness,omitempty"`
+	KeepOnExit     bool                     `mapstructure:"keep_on_exit" yaml:"keep_on_exit,omitempty"`
+	// An LED pulsing by itself to show the daemon is running, off unless a
+	// colour is set. Led is where it sits on the strip, which services skip.
+	Heartbeat      struct {
+		Led    int
+		Colour string
+		Period time.Duration
+	}
+}
+
+// Validate checks the configuration, returning a ConfigError if it is wrong.
+func (c *Config) Validate() error {
+	if err := c.validate(); err != nil {
+		return &ConfigError{err}
+	}
+	return nil
+}
+
+func (c *Config) validate() error {
+	if c.Hertz <= 0 {
+		return fmt.Errorf("hertz must be at least 1 to refresh the strip, not %d", c.Hertz)
+	}
+	if c.Length < 1 {
+		return fmt.Errorf("length must be at least 1 LED, not %d", c.Length)
+	}
+	if c.Channels != 3 && c.Channels != 4 {
+		return fmt.Errorf("channels should be 3 for RGB or 4 for RGBW, not %d", c.Channels)
+	}
+	speed := physic.Frequency(c.SpiSpeedHz) * physic.Hertz
+	if speed < MinSpiSpeed || speed > MaxSpiSpeed {
+		return fmt.Errorf("spi_speed_hz %s is outside the WS281x range %s to %s", speed, MinSpiSpeed, MaxSpiSpeed)
+	}
+	if c.SpiMode < 0 || c.SpiMode > 3 {
+		return fmt.Errorf("spi_mode %d should be 0 to 3", c.SpiMode)
+	}
+	if c.SpiBits <= 0 || c.SpiBits > 32 || c.SpiBits%8 != 0 {
+		return fmt.Errorf("spi_bits %d should be 8, 16, 24 or 32", c.SpiBits)
+	}
+	if c.StartOffset < 0 || c.StartOffset >= c.Length {
+		return fmt.Errorf("start_offset %d leaves no pixels on a strip of %d", c.StartOffset, c.Length)
+	}
+	if c.Gamma < 0 || c.GammaWhite < 0 {
+		return errors.New("gamma and gamma_white can't be negative")
+	}
+	if len(c.WhiteBalance) != 0 && len(c.WhiteBalance) != 3 {
+		return fmt.Errorf("white_balance needs gains for red, green and blue, not %d values", len(c.WhiteBalance))
+	}
+	for _, gain := range c.WhiteBalance {
+		if gain < 0 {
+			return errors.New("white_balance gains can't be negative")
+		}
+	}
+	if c.Heartbeat.Colour != "" {
+		if _, err := colour.Parse(c.Heartbeat.Colour); err != nil {
+			return fmt.Errorf("heartbeat colour: %w", err)
+		}
+		if c.Heartbeat.Led < 0 || c.Heartbeat.Led >= c.Length {
+			return fmt.Errorf("heartbeat led %d is off a strip of %d", c.Heartbeat.Led, c.Length)
+		}
+		if c.Heartbeat.Period <= 0 {
+			return fmt.Errorf("heartbeat period must be positive, not %s", c.Heartbeat.Period)
+		}
+	}
+	if c.MaxBrightness < 0 || c.MaxBrightness > 1 {
+		return fmt.Errorf("max_brightness %g should be from 0 to 1", c.MaxBrightness)
+	}
+	if c.ResetBytes < 0 {
+		return fmt.Errorf("reset_bytes can't be negative, not %d", c.ResetBytes)
+	}
+	if c.MaxPixelSum < 0 || c.MaxStripSum < 0 {
+		return errors.New("max_pixel_sum and max_strip_sum can't be negative")
+	}
+	if _, err := colour.Parse(c.Loading.Colour); err != nil {
+		return fmt.Errorf("loading colour: %w", err)
+	}
+	if c.Loading.Style != "solid" && c.Loading.Style != "chase" {
+		return fmt.Errorf("loading style %q should be solid or chase", c.Loading.Style)
+	}
+	for state, code := range c.Colours {
+		if _, err := colour.Parse(code); err != nil {
+			return fmt.Errorf("colours %s: %w", state, err)
+		}
+	}
+	return nil
+}
+
+// Position maps a pixel's logical index to where it sits on the strip, skipping
+// the first StartOffset pixels and counting from the far end when reversed.
+func (c *Config) Position(i int) int {
+	i += c.StartOffset
+	if c.Reverse {
+		return c.Length - 1 - i
+	}
+	return i
+}
+
+// HeartbeatSlot returns the logical index the heartbeat LED takes from the
+// services, or -1 if it is off or outside their part of the strip.
+func (c *Config) HeartbeatSlot() int {
+	if c.Heartbeat.Colour == "" {
+		return -1
+	}
+	i := c.Heartbeat.Led
+	if c.Reverse {
+		i = c.Length - 1 - i
+	}
+	if i -= c.StartOffset; i < 0 {
+		return -1
+	}
+	return i
+}
+
 type Strip struct {
+	sync.RWMutex
+	Config   *Config
 	Logger   *limlog.Limlog
 	SPIBus   *string
 	HRz      physic.Frequency
 	Channels *int
 	Count    *int
-	Display  *nrzled.Dev
+	Display  io.Writer
 	Pixels   []*led.Led
 	spidev   spi.PortCloser
+
+	gamma, gammaWhite [256]byte     // corrections for the RGB and W channels
+	override          string        // shown on every pixel when set
+	forced            string        // like override, but the watchdog can't clear it
+	started           time.Time     // for animating the loading pattern
+	frameTime         time.Duration // rendering and sending the last frame
+	frameRate         float64       // smoothed frames sent per second
+	lastFrame         time.Time     // when the last frame was sent
+	writeErrors       errorThrottle // keeps a lost SPI device from flooding the log
+	drawn             []byte        // the frame Update last wrote
+	closed            bool
 }
 
-func Init(logger *limlog.Limlog, spibus *string, length *int, channels *int, hertz *int) (*Strip, error) {
+// DryRun stands in for the SPI display, logging each frame at debug level
+// instead of sending it anywhere.
+type DryRun struct {
+	Logger *limlog.Limlog
+	Frames int
+}
+
+func (d *DryRun) Write(buf []byte) (int, error) {
+	d.Frames++
+	d.Logger.Debug("Frame", zap.Int("frame", d.Frames), zap.String("data", hex.EncodeToString(buf)))
+	return len(buf), nil
+}
+
+// Init sets up the strip on the SPI bus, or with dryRun on a DryRun display
+// that never touches the hardware. This is the one place a display is picked.
+func Init(logger *limlog.Limlog, config *Config, dryRun bool) (*Strip, error) {
+	if dryRun {
+		return New(logger, config, &DryRun{Logger: logger})
+	}
+	if err := config.Validate(); err != nil {
+		return nil, err
+	}
+	port, display, err := open(config)
+	if err != nil {
+		return nil, &DeviceError{err}
+	}
+	strip, err := New(logger, config, display)
+	if err != nil {
+		port.Close()
+		return nil, err
+	}
+	strip.spidev = port
+	return strip, nil
+}
+
+// New sets up the strip drawing on any display, such as a simulator or a
+// recorder, and writes the first frame to it.
+func New(logger *limlog.Limlog, config *Config, display io.Writer) (*Strip, error) {
+	if err := config.Validate(); err != nil {
+		return nil, err
+	}
 
 	strip := &Strip{}
+	strip.Config = config
 	strip.Logger = logger
-	strip.SPIBus = spibus
-	strip.Count = length
-	strip.Channels = channels
+	strip.SPIBus = &config.Spidev
+	strip.HRz = physic.Frequency(config.SpiSpeedHz) * physic.Hertz
+	strip.Count = &config.Length
+	strip.Channels = &config.Channels
+	strip.Display = display
+	strip.started = time.Now()
+	strip.writeErrors.interval = writeErrorInterval
+	strip.gamma = gammaTable(config.Gamma)
+	strip.gammaWhite = strip.gamma
+	if config.GammaWhite > 0 {
+		strip.gammaWhite = gammaTable(config.GammaWhite)
+	}
+	_, _ = strip.Display.Write(strip.Buffer())
 
+	return strip, nil
+}
+
+// open connects nrzled to the configured SPI device.
+func open(config *Config) (spi.PortCloser, io.Writer, error) {
 	if _, err := host.Init(); err != nil {
-		return nil, errors.New("Unable to intialize the pariph.Host.")
+		return nil, nil, errors.New("Unable to intialize the pariph.Host.")
 	}
 
-	var err error
-	if strip.spidev, err = spireg.Open(*strip.SPIBus); err != nil {
-		return nil, err
+	spidev, err := spireg.Open(config.Spidev)
+	if err != nil {
+		return nil, nil, err
 	}
-	//defer s.Close()
 
-	if _, ok := strip.spidev.(spi.Pins); ok {
+	if _, ok := spidev.(spi.Pins); ok {
 		//		strip.Logger.Infof("Using pins: %i, %i ,%i", p.CLK(), p.MOSI(), p.MISO())
 	}
 	o := nrzled.Opts{
-		NumPixels: *strip.Count,
-		Channels:  *strip.Channels,
-		Freq:      2500 * physic.KiloHertz,
+		NumPixels: config.Length,
+		Channels:  config.Channels,
+		Freq:      physic.Frequency(config.SpiSpeedHz) * physic.Hertz,
 	}
-	strip.Display, err = nrzled.NewSPI(strip.spidev, &o)
+	port := &configuredPort{spidev, spi.Mode(config.SpiMode), config.SpiBits, config.ResetBytes}
+	display, err := nrzled.NewSPI(port, &o)
 	if err != nil {
-		return nil, err
+		spidev.Close()
+		return nil, nil, err
 	}
-	_, _ = strip.Display.Write(bytes.Repeat(Loading, *strip.Count-1))
+	return spidev, display, nil
+}
 
-	return strip, nil
+// configuredPort connects with the configured SPI mode and word size,
+// whatever nrzled asks for, keeping any flags such as NoCS, and sends the
+// configured number of zero bytes ahead of every frame.
+type configuredPort struct {
+	spi.PortCloser
+	mode  spi.Mode
+	bits  int
+	reset int
+}
+
+func (p *configuredPort) Connect(f physic.Frequency, mode spi.Mode, bits int) (spi.Conn, error) {
+	c, err := p.PortCloser.Connect(f, mode&^spi.Mode3|p.mode, p.bits)
+	if err != nil || p.reset == 0 {
+		return c, err
+	}
+	return &resetConn{c, p.reset}, nil
+}
+
+// resetConn prefixes every transfer with reset zero bytes, which some
+// controllers need before the first LED's data comes out clean.
+type resetConn struct {
+	spi.Conn
+	reset int
+}
+
+func (c *resetConn) Tx(w, r []byte) error {
+	wr := make([]byte, c.reset+len(w))
+	copy(wr[c.reset:], w)
+	if r == nil {
+		return c.Conn.Tx(wr, nil)
+	}
+	rr := make([]byte, len(wr))
+	err := c.Conn.Tx(wr, rr)
+	copy(r, rr[c.reset:])
+	return err
 }
 
 func (strip *Strip) Add(unit string) (pixel *led.Led, err error) {
 	led := &led.Led{}
 	led.Unit = unit
 
-	if len(strip.Pixels) == *strip.Count {
-		return nil, errors.New("Already at one service per pixel.")
-	} else {
-		strip.Pixels = append(strip.Pixels, led)
-		led.Number = len(strip.Pixels)
-		return led, nil
+	strip.Lock()
+	defer strip.Unlock()
+	if len(strip.Pixels) >= strip.slots() {
+		return nil, ErrFull
 	}
+	strip.Pixels = append(strip.Pixels, led)
+	led.Number = strip.number(len(strip.Pixels) - 1)
 	return led, nil
 }
 
+// slots returns how many pixels services can have, which is the strip after
+// start_offset less the heartbeat LED if it falls there. The caller holds the
+// lock.
+func (s *Strip) slots() int {
+	slots := *s.Count - s.Config.StartOffset
+	if s.Config.HeartbeatSlot() >= 0 {
+		slots--
+	}
+	return slots
+}
+
+// number returns the Number of the i-th service pixel, stepping over the
+// heartbeat LED. The caller holds the lock.
+func (s *Strip) number(i int) int {
+	if heartbeat := s.Config.HeartbeatSlot(); heartbeat >= 0 && i >= heartbeat {
+		i++
+	}
+	return i + 1
+}
+
+// Led returns the pixel showing unit, or nil if there isn't one. Case and
+// surrounding space are ignored, as a hand written unit name may differ.
+func (s *Strip) Led(unit string) *led.Led {
+	unit = strings.TrimSpace(unit)
+	s.RLock()
+	defer s.RUnlock()
+	for _, p := range s.Pixels {
+		if strings.EqualFold(p.Unit, unit) {
+			return p
+		}
+	}
+	return nil
+}
+
+// SetColourByUnit sets a steady colour on the pixel showing unit, for driving
+// the strip from outside the systemd subscription.
+func (s *Strip) SetColourByUnit(unit string, code string) error {
+	if _, err := colour.Parse(code); err != nil {
+		return err
+	}
+	p := s.Led(unit)
+	if p == nil {
+		return fmt.Errorf("%w %s", ErrUnknownUnit, unit)
+	}
+	p.Drive(code)
+	return nil
+}
+
+// SetColours sets the pixels in the order they were added from codes, all in
+// one go so no frame shows half of them. It takes no fewer locks than setting
+// them one by one: each pixel is still driven under its own lock, as states
+// are applied and read under it, with the strip lock held on top. Pixels past
+// the end of codes are left alone.
+func (s *Strip) SetColours(codes []string) error {
+	for _, code := range codes {
+		if _, err := colour.Parse(code); err != nil {
+			return err
+		}
+	}
+	s.Lock()
+	defer s.Unlock()
+	if len(codes) > len(s.Pixels) {
+		return fmt.Errorf("%d colours for %d pixels", len(codes), len(s.Pixels))
+	}
+	for i, code := range codes {
+		s.Pixels[i].Drive(code)
+	}
+	return nil
+}
+
+// SetAll sets every pixel to the colour, as SetColourByUnit does for one.
+func (s *Strip) SetAll(code string) error {
+	if _, err := colour.Parse(code); err != nil {
+		return err
+	}
+	for _, p := range s.Leds() {
+		p.Drive(code)
+	}
+	return nil
+}
+
+// Leds returns the pixels currently assigned to services.
+func (s *Strip) Leds() []*led.Led {
+	s.RLock()
+	defer s.RUnlock()
+	return append([]*led.Led(nil), s.Pixels...)
+}
+
+// SnapshotColours returns the colour each LED is set to, in order along the
+// strip, with LEDs no service uses left off. Animations aren't applied.
+func (s *Strip) SnapshotColours() []colour.Colour {
+	s.RLock()
+	defer s.RUnlock()
+	colours := make([]colour.Colour, *s.Count)
+	for _, p := range s.Pixels {
+		c, _ := colour.Parse(p.CurrentColour())
+		colours[s.Config.Position(p.Number-1)] = c
+	}
+	return colours
+}
+
+// Buffer renders the pixels into the bytes handed to nrzled, one byte per
+// channel per LED. RGB strips have no white LED, so white is folded into the
+// other three channels instead of being dropped. The power caps and then
+// max_brightness are applied last, to the bytes actually sent.
+func (s *Strip) Buffer() []byte {
+	buf := s.render()
+	s.heartbeat(buf, time.Now())
+	if s.Config.MaxPixelSum > 0 {
+		channels := *s.Channels
+		for offset := 0; offset < len(buf); offset += channels {
+			limit(buf[offset:offset+channels], s.Config.MaxPixelSum)
+		}
+	}
+	if s.Config.MaxStripSum > 0 {
+		limit(buf, s.Config.MaxStripSum)
+	}
+	if m := s.Config.MaxBrightness; m > 0 && m < 1 {
+		for i, v := range buf {
+			buf[i] = dim(v, m)
+		}
+	}
+	return buf
+}
+
+// heartbeat draws the heartbeat LED into buf, unless every LED is overridden.
+// With animations disabled it shows its colour steadily like the rest.
+func (s *Strip) heartbeat(buf []byte, now time.Time) {
+	s.RLock()
+	defer s.RUnlock()
+	hb := s.Config.Heartbeat
+	if hb.Colour == "" || s.forcedOrOverride() != "" {
+		return
+	}
+	c, _ := colour.Parse(hb.Colour)
+	if !s.Config.DisableAnimations {
+		pulse := led.Pulse{MinScale: 0.1, MaxScale: 1, Period: hb.Period}
+		c = pulse.Apply(c, now.Sub(s.started))
+	}
+	channels := *s.Channels
+	s.encode(buf[hb.Led*channels:], c)
+}
+
+func (s *Strip) render() []byte {
+	now := time.Now()
+
+	s.RLock()
+	defer s.RUnlock()
+	channels := *s.Channels
+	buf := make([]byte, *s.Count*channels)
+	if code := s.forcedOrOverride(); code != "" {
+		c, _ := colour.Parse(code)
+		for offset := 0; offset < len(buf); offset += channels {
+			s.encode(buf[offset:], c)
+		}
+		return buf
+	}
+	if s.loading() {
+		c, _ := colour.Parse(s.Config.Loading.Colour)
+		step := int(now.Sub(s.started)/chaseStep) % *s.Count
+		for i := 0; i < *s.Count; i++ {
+			if s.Config.Loading.Style == "chase" && i != step {
+				continue
+			}
+			s.encode(buf[i*channels:], c)
+		}
+		return buf
+	}
+	for _, p := range s.Pixels {
+		offset := s.Config.Position(p.Number-1) * channels
+		var c colour.Colour
+		if s.Config.DisableAnimations {
+			c, _ = colour.Parse(p.CurrentColour()) // unset pixels stay off
+		} else {
+			c = p.Frame(now)
+		}
+		s.encode(buf[offset:], c)
+	}
+	return buf
+}
+
+// loading reports whether any service pixel is still waiting for its first
+// state or colour, in which case the loading pattern covers the strip. The
+// caller holds the read lock.
+func (s *Strip) loading() bool {
+	if len(s.Pixels) == 0 {
+		return true
+	}
+	for _, p := range s.Pixels {
+		if !p.IsSet() {
+			return true
+		}
+	}
+	return false
+}
+
+// encode writes one pixel at the start of buf, white balanced and then gamma
+// corrected.
+func (s *Strip) encode(buf []byte, c colour.Colour) {
+	r, g, b, w := c.R, c.G, c.B, c.W
+	if wb := s.Config.WhiteBalance; len(wb) == 3 {
+		r, g, b = dim(r, wb[0]), dim(g, wb[1]), dim(b, wb[2])
+	}
+	r, g, b, w = s.gamma[r], s.gamma[g], s.gamma[b], s.gammaWhite[w]
+	if *s.Channels == 3 {
+		rgb := colour.Colour{R: fold(r, w), G: fold(g, w), B: fold(b, w)}.RGB()
+		copy(buf, rgb[:])
+	} else {
+		rgbw := colour.Colour{R: r, G: g, B: b, W: w}.RGBW()
+		copy(buf, rgbw[:])
+	}
+}
+
+// forcedOrOverride is the colour for every pixel, if any. The caller holds
+// the lock.
+func (s *Strip) forcedOrOverride() string {
+	if s.forced != "" {
+		return s.forced
+	}
+	return s.override
+}
+
+// Force shows the colour on every pixel for good, over the services and any
+// Override, e.g. to check the wiring on a desk.
+func (s *Strip) Force(code string) {
+	s.Lock()
+	defer s.Unlock()
+	s.forced = code
+}
+
+// Override shows the colour on every pixel of the strip in place of the
+// services, until called again with an empty colour.
+func (s *Strip) Override(code string) {
+	s.Lock()
+	defer s.Unlock()
+	s.override = code
+}
+
+// gammaTable maps every channel value through the gamma curve. A gamma of 0,
+// meaning unset, leaves values as they are.
+func gammaTable(gamma float64) (table [256]byte) {
+	for i := range table {
+		if gamma == 0 {
+			table[i] = byte(i)
+		} else {
+			table[i] = byte(math.Round(0xff * math.Pow(float64(i)/0xff, gamma)))
+		}
+	}
+	return table
+}
+
+// dim scales a channel's brightness, saturating at full.
+func dim(c byte, scale float64) byte {
+	if v := float64(c) * scale; v < 0xff {
+		return byte(v)
+	}
+	return 0xff
+}
+
+// limit scales buf down evenly so its values add up to no more than budget,
+// keeping the ratios between channels and so the hue.
+func limit(buf []byte, budget int) {
+	sum := 0
+	for _, v := range buf {
+		sum += int(v)
+	}
+	if sum <= budget {
+		return
+	}
+	for i, v := range buf {
+		buf[i] = byte(int(v) * budget / sum)
+	}
+}
+
+// fold adds the white component to a colour channel, saturating at full.
+func fold(c, w byte) byte {
+	if int(c)+int(w) > 0xff {
+		return 0xff
+	}
+	return c + w
+}
+
+// UpdateLoop writes a frame at the configured rate until the strip is closed.
 func (s *Strip) UpdateLoop() {
-	buf := make([]byte, 5*4)
 	for {
-		for _, p := range s.Pixels {
-			offset := (p.Number - 1) * 4
-			rgba, _ := strconv.ParseUint(p.Colour, 16, 32)
-			buf[offset] = byte(rgba >> 24)
-			buf[offset+1] = byte(rgba >> 16)
-			buf[offset+2] = byte(rgba >> 8)
-			buf[offset+3] = byte(rgba)
+		err := s.Update()
+		if errors.Is(err, ErrClosed) {
+			return
+		}
+		s.Lock()
+		if log, suppressed := s.writeErrors.report(err, time.Now()); log {
+			s.Logger.Error("Failed to write frame", zap.Error(err), zap.Int("suppressed", suppressed))
+		}
+		interval := time.Second / time.Duration(s.Config.Hertz)
+		s.Unlock()
+		time.Sleep(interval)
+	}
+}
+
+// Run is UpdateLoop until ctx is done, when it closes the strip, turning the
+// LEDs off, and returns once the loop has stopped.
+func (s *Strip) Run(ctx context.Context) error {
+	stopped := make(chan struct{})
+	go func() {
+		s.UpdateLoop()
+		close(stopped)
+	}()
+	<-ctx.Done()
+	err := s.Close()
+	<-stopped
+	return err
+}
+
+// Update writes one frame to the display, for driving the strip from a loop
+// of your own instead of UpdateLoop.
+func (s *Strip) Update() error {
+	start := time.Now()
+	buf := s.Buffer()
+	s.Lock()
+	defer s.Unlock()
+	if s.closed {
+		return ErrClosed
+	}
+	_, err := s.Display.Write(buf)
+	s.recordFrame(start, time.Now())
+	if err == nil {
+		s.drawn = buf
+	}
+	return err
+}
+
+// NeedsUpdate reports whether the next frame would differ from the one last
+// written by Update, as a colour changed or an animation moved on.
+func (s *Strip) NeedsUpdate() bool {
+	buf := s.Buffer()
+	s.RLock()
+	defer s.RUnlock()
+	return !bytes.Equal(buf, s.drawn)
+}
+
+// How often the same frame write error is logged while it keeps happening.
+const writeErrorInterval = time.Minute
+
+// errorThrottle collapses a run of identical errors into one report per
+// interval, counting the ones held back.
+type errorThrottle struct {
+	interval   time.Duration
+	last       string
+	reported   time.Time
+	suppressed int
+}
+
+// report says whether err should be logged now, and how many identical errors
+// were held back since the last time it was. A nil err ends the run.
+func (t *errorThrottle) report(err error, now time.Time) (bool, int) {
+	if err == nil {
+		t.last, t.suppressed = "", 0
+		return false, 0
+	}
+	if err.Error() == t.last && now.Sub(t.reported) < t.interval {
+		t.suppressed++
+		return false, 0
+	}
+	suppressed := t.suppressed
+	t.last, t.reported, t.suppressed = err.Error(), now, 0
+	return true, suppressed
+}
+
+// SetFrequency changes how many frames a second UpdateLoop writes, from the
+// next frame on.
+func (s *Strip) SetFrequency(hertz int) error {
+	if hertz <= 0 {
+		return &ConfigError{fmt.Errorf("hertz must be at least 1 to refresh the strip, not %d", hertz)}
+	}
+	s.Lock()
+	defer s.Unlock()
+	s.Config.Hertz = hertz
+	return nil
+}
+
+// Resize changes how many LEDs the strip has, e.g. after more were fitted.
+// The whole strip is blanked first so LEDs past a shorter end go dark, and
+// pixels that no longer fit lose their units. The rest are numbered again, as
+// with reverse or a heartbeat their places depend on the length. The SPI
+// display is reopened for the new length.
+func (s *Strip) Resize(length int) error {
+	s.Lock()
+	defer s.Unlock()
+	if length < 1 {
+		return &ConfigError{fmt.Errorf("length must be at least 1 LED, not %d", length)}
+	}
+	if length <= s.Config.StartOffset {
+		return &ConfigError{fmt.Errorf("start_offset %d leaves no pixels on a strip of %d", s.Config.StartOffset, length)}
+	}
+	if hb := s.Config.Heartbeat; hb.Colour != "" && hb.Led >= length {
+		return &ConfigError{fmt.Errorf("heartbeat led %d is off a strip of %d", hb.Led, length)}
+	}
+	if err := s.allOff(); err != nil {
+		return err
+	}
+	s.Config.Length = length
+	if fit := s.slots(); len(s.Pixels) > fit {
+		s.Pixels = s.Pixels[:fit]
+	}
+	for i, p := range s.Pixels {
+		p.Lock()
+		p.Number = s.number(i)
+		p.Unlock()
+	}
+	if s.spidev == nil {
+		return nil
+	}
+	s.spidev.Close()
+	port, display, err := open(s.Config)
+	if err != nil {
+		return &DeviceError{err}
+	}
+	s.spidev, s.Display = port, display
+	return nil
+}
+
+// frameSmoothing is the weight of the latest frame in the frame rate.
+const frameSmoothing = 0.1
+
+// recordFrame notes the timing of a frame started and sent at the times
+// given. The caller holds the lock.
+func (s *Strip) recordFrame(start, sent time.Time) {
+	s.frameTime = sent.Sub(start)
+	if !s.lastFrame.IsZero() {
+		rate := float64(time.Second) / float64(sent.Sub(s.lastFrame))
+		if s.frameRate == 0 {
+			s.frameRate = rate
+		} else {
+			s.frameRate += frameSmoothing * (rate - s.frameRate)
+		}
+	}
+	s.lastFrame = sent
+}
+
+// FrameStats returns how long the last frame took to render and send, and
+// the smoothed rate frames are actually going out at.
+func (s *Strip) FrameStats() (time.Duration, float64) {
+	s.RLock()
+	defer s.RUnlock()
+	return s.frameTime, s.frameRate
+}
+
+// AllOff writes a single frame with every LED off straight away. The pixels
+// keep their states, so a running UpdateLoop draws them again on its next
+// frame; Close turns them off for good.
+func (s *Strip) AllOff() error {
+	s.Lock()
+	defer s.Unlock()
+	return s.allOff()
+}
+
+// allOff is AllOff for a caller holding the lock.
+func (s *Strip) allOff() error {
+	channels := *s.Channels
+	_, err := s.Display.Write(make([]byte, *s.Count*channels))
+	return err
+}
+
+// Close turns every LED off and releases the SPI port, stopping UpdateLoop.
+// The off frame goes to the display like any other, so nrzled encodes and
+// latches it and the LEDs really go dark. With keep_on_exit the LEDs are left
+// showing the last frame.
+func (s *Strip) Close() error {
+	s.Lock()
+	defer s.Unlock()
+	if s.closed {
+		return nil
+	}
+	s.closed = true
+	var err error
+	if !s.Config.KeepOnExit {
+		err = s.allOff()
+	}
+	if s.spidev != nil {
+		if cerr := s.spidev.Close(); err == nil {
+			err = cerr
 		}
-		_, _ = s.Display.Write(buf)
-		time.Sleep(5 * time.Second)
 	}
+	return err
 }
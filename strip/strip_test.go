@@ -0,0 +1,762 @@
+package strip
+
+import (
+	"bytes"
+	"context"
+	"errors"
+	"fmt"
+	"io"
+	"sync"
+	"testing"
+	"time"
+
+	"github.com/jar-o/limlog"
+	"github.com/shift/systemd-status-leds/led"
+	"go.uber.org/zap"
+	"periph.io/x/conn/v3/physic"
+	"periph.io/x/conn/v3/spi"
+)
+
+// testConfig is a small RGB strip that passes Validate.
+func testConfig(length int) *Config {
+	c := &Config{
+		Length:     length,
+		Channels:   3,
+		Hertz:      10,
+		SpiSpeedHz: int(DefaultSpiSpeed / physic.Hertz),
+		SpiMode:    DefaultSpiMode,
+		SpiBits:    DefaultSpiBits,
+	}
+	c.Loading.Colour = DefaultLoadingColour
+	c.Loading.Style = "solid"
+	return c
+}
+
+func newTestStrip(t testing.TB, c *Config, display io.Writer) *Strip {
+	t.Helper()
+	s, err := New(nil, c, display)
+	if err != nil {
+		t.Fatalf("New: %v", err)
+	}
+	return s
+}
+
+func numbers(s *Strip) []int {
+	var n []int
+	for _, p := range s.Leds() {
+		n = append(n, p.Number)
+	}
+	return n
+}
+
+func equalInts(a, b []int) bool {
+	if len(a) != len(b) {
+		return false
+	}
+	for i := range a {
+		if a[i] != b[i] {
+			return false
+		}
+	}
+	return true
+}
+
+func TestResizeHeartbeat(t *testing.T) {
+	tests := []struct {
+		name    string
+		length  int
+		led     int
+		reverse bool
+		resize  int
+		err     bool
+		want    []int
+	}{
+		{name: "heartbeat past the new end", length: 5, led: 4, resize: 3, err: true, want: []int{1, 2, 3, 4}},
+		{name: "heartbeat kept clear", length: 5, led: 1, resize: 4, want: []int{1, 3, 4}},
+		{name: "heartbeat moves when reversed", length: 5, led: 1, reverse: true, resize: 4, want: []int{1, 2, 4}},
+		{name: "growing", length: 3, led: 0, resize: 6, want: []int{2, 3}},
+	}
+	for _, tt := range tests {
+		t.Run(tt.name, func(t *testing.T) {
+			c := testConfig(tt.length)
+			c.Reverse = tt.reverse
+			c.Heartbeat.Led = tt.led
+			c.Heartbeat.Colour = "000011"
+			c.Heartbeat.Period = time.Second
+			s := newTestStrip(t, c, io.Discard)
+			for {
+				if _, err := s.Add("unit"); err != nil {
+					break
+				}
+			}
+			err := s.Resize(tt.resize)
+			var configErr *ConfigError
+			if tt.err != errors.As(err, &configErr) {
+				t.Fatalf("Resize(%d) = %v, want a ConfigError: %t", tt.resize, err, tt.err)
+			}
+			if got := numbers(s); !equalInts(got, tt.want) {
+				t.Errorf("numbers after Resize(%d) = %v, want %v", tt.resize, got, tt.want)
+			}
+			slot := s.Config.HeartbeatSlot()
+			for _, p := range s.Leds() {
+				if p.Number-1 == slot {
+					t.Errorf("pixel %d sits on the heartbeat", p.Number)
+				}
+			}
+			if got := len(s.Buffer()); got != s.Config.Length*3 {
+				t.Errorf("frame is %d bytes, want %d", got, s.Config.Length*3)
+			}
+		})
+	}
+}
+
+func TestSettersEndLoading(t *testing.T) {
+	tests := []struct {
+		name string
+		set  func(s *Strip) error
+		want []byte
+	}{
+		{
+			name: "SetColourByUnit",
+			set: func(s *Strip) error {
+				if err := s.SetColourByUnit("a.service", "ff0000"); err != nil {
+					return err
+				}
+				return s.SetColourByUnit("b.service", "00ff00")
+			},
+			want: []byte{0xff, 0, 0, 0, 0xff, 0, 0, 0, 0},
+		},
+		{
+			name: "SetColours",
+			set:  func(s *Strip) error { return s.SetColours([]string{"ff0000", "00ff00"}) },
+			want: []byte{0xff, 0, 0, 0, 0xff, 0, 0, 0, 0},
+		},
+		{
+			name: "SetAll",
+			set:  func(s *Strip) error { return s.SetAll("0000ff") },
+			want: []byte{0, 0, 0xff, 0, 0, 0xff, 0, 0, 0},
+		},
+	}
+	for _, tt := range tests {
+		t.Run(tt.name, func(t *testing.T) {
+			s := newTestStrip(t, testConfig(3), io.Discard)
+			for _, unit := range []string{"a.service", "b.service"} {
+				p, err := s.Add(unit)
+				if err != nil {
+					t.Fatalf("Add(%s): %v", unit, err)
+				}
+				p.Animation = &led.Pulse{MinScale: 0, MaxScale: 1, Period: time.Second} // must not outlive the setters
+			}
+			if err := tt.set(s); err != nil {
+				t.Fatalf("%s: %v", tt.name, err)
+			}
+			if got := s.Buffer(); !bytes.Equal(got, tt.want) {
+				t.Errorf("frame = %x, want %x", got, tt.want)
+			}
+		})
+	}
+}
+
+func TestLimit(t *testing.T) {
+	tests := []struct {
+		name   string
+		buf    []byte
+		budget int
+		want   []byte
+	}{
+		{name: "under budget", buf: []byte{10, 20, 30}, budget: 100, want: []byte{10, 20, 30}},
+		{name: "at budget", buf: []byte{10, 20, 30}, budget: 60, want: []byte{10, 20, 30}},
+		{name: "halved", buf: []byte{100, 50, 0}, budget: 75, want: []byte{50, 25, 0}},
+		{name: "full white", buf: []byte{0xff, 0xff, 0xff}, budget: 0xff, want: []byte{85, 85, 85}},
+		{name: "rounds down", buf: []byte{3, 3, 3}, budget: 4, want: []byte{1, 1, 1}},
+		{name: "zero budget", buf: []byte{1, 2, 3}, budget: 0, want: []byte{0, 0, 0}},
+		{name: "empty", buf: []byte{}, budget: 0, want: []byte{}},
+	}
+	for _, tt := range tests {
+		buf := append([]byte(nil), tt.buf...)
+		limit(buf, tt.budget)
+		if !bytes.Equal(buf, tt.want) {
+			t.Errorf("%s: limit(%v, %d) = %v, want %v", tt.name, tt.buf, tt.budget, buf, tt.want)
+		}
+		sum := 0
+		for _, v := range buf {
+			sum += int(v)
+		}
+		if sum > tt.budget && sum > 0 {
+			t.Errorf("%s: sum %d is over budget %d", tt.name, sum, tt.budget)
+		}
+	}
+}
+
+func TestErrorThrottleReport(t *testing.T) {
+	lost := errors.New("device lost")
+	busy := errors.New("device busy")
+	start := time.Now()
+	steps := []struct {
+		err        error
+		at         time.Duration
+		log        bool
+		suppressed int
+	}{
+		{err: lost, at: 0, log: true},
+		{err: lost, at: time.Second, log: false},
+		{err: lost, at: 2 * time.Second, log: false},
+		{err: lost, at: time.Minute, log: true, suppressed: 2},
+		{err: busy, at: time.Minute + time.Second, log: true},
+		{err: lost, at: time.Minute + 2*time.Second, log: true},
+		{err: lost, at: time.Minute + 3*time.Second, log: false},
+		{err: nil, at: time.Minute + 4*time.Second, log: false},
+		{err: lost, at: time.Minute + 5*time.Second, log: true},
+	}
+	throttle := errorThrottle{interval: time.Minute}
+	for i, step := range steps {
+		log, suppressed := throttle.report(step.err, start.Add(step.at))
+		if log != step.log || suppressed != step.suppressed {
+			t.Errorf("step %d: report(%v) = %t, %d, want %t, %d", i, step.err, log, suppressed, step.log, step.suppressed)
+		}
+	}
+}
+
+// benchStrip is a strip of length pixels, each with a unit, and a colour code
+// for each.
+func benchStrip(b *testing.B, length int) (*Strip, []string) {
+	s := newTestStrip(b, testConfig(length), io.Discard)
+	codes := make([]string, length)
+	for i := range codes {
+		codes[i] = fmt.Sprintf("%02x%02x%02x", i, 0xff-i, i/2)
+		if _, err := s.Add(fmt.Sprintf("unit-%d.service", i)); err != nil {
+			b.Fatalf("Add: %v", err)
+		}
+	}
+	return s, codes
+}
+
+func BenchmarkSetColours(b *testing.B) {
+	s, codes := benchStrip(b, 144)
+	b.ResetTimer()
+	for i := 0; i < b.N; i++ {
+		if err := s.SetColours(codes); err != nil {
+			b.Fatal(err)
+		}
+	}
+}
+
+// BenchmarkDrive sets the same frame a pixel at a time without the strip lock,
+// which is what SetColours pays on top of for the frame being whole.
+func BenchmarkDrive(b *testing.B) {
+	s, codes := benchStrip(b, 144)
+	b.ResetTimer()
+	for i := 0; i < b.N; i++ {
+		for j, p := range s.Leds() {
+			p.Drive(codes[j])
+		}
+	}
+}
+
+func TestSetColours(t *testing.T) {
+	s := newTestStrip(t, testConfig(4), io.Discard)
+	for _, unit := range []string{"a.service", "b.service", "c.service"} {
+		if _, err := s.Add(unit); err != nil {
+			t.Fatalf("Add(%s): %v", unit, err)
+		}
+	}
+	if err := s.SetColours([]string{"ff0000", "00ff00", "0000ff"}); err != nil {
+		t.Fatalf("SetColours: %v", err)
+	}
+	if err := s.SetColours([]string{"ffffff", "nope"}); err == nil {
+		t.Error("SetColours with a bad colour succeeded")
+	}
+	if err := s.SetColours([]string{"111", "222", "333", "444"}); err == nil {
+		t.Error("SetColours with more colours than pixels succeeded")
+	}
+	want := []string{"ff000000", "00ff0000", "0000ff00", "00000000"}
+	for i, c := range s.SnapshotColours() {
+		if c.String() != want[i] {
+			t.Errorf("LED %d is %s, want %s", i, c, want[i])
+		}
+	}
+}
+
+func TestHeartbeatAnimates(t *testing.T) {
+	for _, disabled := range []bool{false, true} {
+		c := testConfig(4)
+		c.DisableAnimations = disabled
+		c.Heartbeat.Led = 3
+		c.Heartbeat.Colour = "ff0000"
+		c.Heartbeat.Period = time.Second
+		s := newTestStrip(t, c, io.Discard)
+		p, err := s.Add("a.service")
+		if err != nil {
+			t.Fatalf("Add: %v", err)
+		}
+		p.ApplyState("active", led.Look{Colour: "00ff00"})
+
+		var frames [][]byte
+		for _, at := range []time.Duration{0, 250 * time.Millisecond, 500 * time.Millisecond} {
+			buf := s.render()
+			s.heartbeat(buf, s.started.Add(at))
+			frames = append(frames, buf)
+		}
+		for i, frame := range frames[1:] {
+			if !bytes.Equal(frame[:3], frames[0][:3]) {
+				t.Errorf("disable_animations %t: service LED went from %x to %x", disabled, frames[0][:3], frame[:3])
+			}
+			if changed := !bytes.Equal(frame[9:], frames[i][9:]); changed == disabled {
+				t.Errorf("disable_animations %t: heartbeat went from %x to %x", disabled, frames[i][9:], frame[9:])
+			}
+		}
+	}
+}
+
+// frame is the buffer a strip configured by c shows with a pixel for each of
+// codes, set to it.
+func frame(t *testing.T, c *Config, codes ...string) []byte {
+	t.Helper()
+	s := newTestStrip(t, c, io.Discard)
+	for i, code := range codes {
+		p, err := s.Add(fmt.Sprintf("unit-%d.service", i))
+		if err != nil {
+			t.Fatalf("Add: %v", err)
+		}
+		p.ApplyState("active", led.Look{Colour: code})
+	}
+	return s.Buffer()
+}
+
+func TestBufferRGB(t *testing.T) {
+	got := frame(t, testConfig(3), "10203040", "ff0000")
+	want := []byte{0x50, 0x60, 0x70, 0xff, 0, 0, 0, 0, 0}
+	if !bytes.Equal(got, want) {
+		t.Errorf("frame = %x, want %x with white folded into red, green and blue", got, want)
+	}
+}
+
+func TestBufferReverse(t *testing.T) {
+	c := testConfig(3)
+	c.Reverse = true
+	got := frame(t, c, "ff0000")
+	want := []byte{0, 0, 0, 0, 0, 0, 0xff, 0, 0}
+	if !bytes.Equal(got, want) {
+		t.Errorf("reversed frame = %x, want %x with position 0 last", got, want)
+	}
+}
+
+func TestBufferStartOffset(t *testing.T) {
+	c := testConfig(4)
+	c.Channels = 4
+	c.StartOffset = 2
+	got := frame(t, c, "ff000011")
+	want := []byte{0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0, 0, 0x11, 0, 0, 0, 0}
+	if !bytes.Equal(got, want) {
+		t.Errorf("frame with start_offset 2 = %x, want %x with the first LED at byte 8", got, want)
+	}
+}
+
+func TestBufferChannels(t *testing.T) {
+	codes := []string{"10203040", "ff000000"}
+	tests := []struct {
+		channels int
+		want     []byte
+	}{
+		{channels: 3, want: []byte{0x50, 0x60, 0x70, 0xff, 0, 0}},
+		{channels: 4, want: []byte{0x10, 0x20, 0x30, 0x40, 0xff, 0, 0, 0}},
+	}
+	for _, tt := range tests {
+		c := testConfig(2)
+		c.Channels = tt.channels
+		if got := frame(t, c, codes...); !bytes.Equal(got, tt.want) {
+			t.Errorf("%d channel frame = %x, want %x", tt.channels, got, tt.want)
+		}
+	}
+}
+
+func TestBufferGammaWhite(t *testing.T) {
+	c := testConfig(1)
+	c.Channels = 4
+	c.Gamma = 2.2
+	c.GammaWhite = 1
+	got := frame(t, c, "80808080")
+	rgb := gammaTable(2.2)[0x80]
+	want := []byte{rgb, rgb, rgb, 0x80}
+	if !bytes.Equal(got, want) {
+		t.Errorf("frame = %x, want %x with white on its own curve", got, want)
+	}
+	if rgb == 0x80 {
+		t.Error("gamma 2.2 left 0x80 alone")
+	}
+}
+
+func TestBufferWhiteBalance(t *testing.T) {
+	c := testConfig(1)
+	c.WhiteBalance = []float64{1, 0.5, 0.25}
+	got := frame(t, c, "ffffff")
+	want := []byte{0xff, 0x7f, 0x3f}
+	if !bytes.Equal(got, want) {
+		t.Errorf("white balanced frame = %x, want %x", got, want)
+	}
+}
+
+func TestBufferMaxBrightness(t *testing.T) {
+	c := testConfig(1)
+	c.MaxBrightness = 0.5
+	got := frame(t, c, "ffffff")
+	want := []byte{0x7f, 0x7f, 0x7f}
+	if !bytes.Equal(got, want) {
+		t.Errorf("full white with max_brightness 0.5 = %x, want %x", got, want)
+	}
+}
+
+// fakeConn records the bytes written to it and reads back their positions.
+type fakeConn struct {
+	spi.Conn
+	written [][]byte
+}
+
+func (c *fakeConn) Tx(w, r []byte) error {
+	c.written = append(c.written, append([]byte(nil), w...))
+	for i := range r {
+		r[i] = byte(i)
+	}
+	return nil
+}
+
+func TestResetConnTx(t *testing.T) {
+	conn := &fakeConn{}
+	reset := &resetConn{conn, 3}
+	if err := reset.Tx([]byte{1, 2}, nil); err != nil {
+		t.Fatalf("Tx: %v", err)
+	}
+	r := make([]byte, 2)
+	if err := reset.Tx([]byte{1, 2}, r); err != nil {
+		t.Fatalf("Tx: %v", err)
+	}
+	for i, w := range conn.written {
+		if want := []byte{0, 0, 0, 1, 2}; !bytes.Equal(w, want) {
+			t.Errorf("transfer %d wrote %v, want %v", i, w, want)
+		}
+	}
+	if want := []byte{3, 4}; !bytes.Equal(r, want) {
+		t.Errorf("read %v, want %v without the reset bytes", r, want)
+	}
+}
+
+// fakePort hands out fakeConns, noting how it was asked to connect.
+type fakePort struct {
+	spi.PortCloser
+	mode spi.Mode
+	bits int
+}
+
+func (p *fakePort) Connect(f physic.Frequency, mode spi.Mode, bits int) (spi.Conn, error) {
+	p.mode, p.bits = mode, bits
+	return &fakeConn{}, nil
+}
+
+func TestConfiguredPortConnect(t *testing.T) {
+	for _, reset := range []int{0, 4} {
+		port := &fakePort{}
+		configured := &configuredPort{port, spi.Mode0, 16, reset}
+		conn, err := configured.Connect(DefaultSpiSpeed, spi.Mode3|spi.NoCS, 8)
+		if err != nil {
+			t.Fatalf("Connect: %v", err)
+		}
+		if port.mode != spi.Mode0|spi.NoCS || port.bits != 16 {
+			t.Errorf("connected with mode %v and %d bits, want mode 0 keeping NoCS and 16 bits", port.mode, port.bits)
+		}
+		if _, ok := conn.(*resetConn); ok != (reset > 0) {
+			t.Errorf("reset_bytes %d gave a %T", reset, conn)
+		}
+	}
+}
+
+// slowDisplay takes a millisecond to write each frame.
+type slowDisplay struct{}
+
+func (slowDisplay) Write(buf []byte) (int, error) {
+	time.Sleep(time.Millisecond)
+	return len(buf), nil
+}
+
+func TestFrameStats(t *testing.T) {
+	s := newTestStrip(t, testConfig(3), slowDisplay{})
+	if took, rate := s.FrameStats(); took != 0 || rate != 0 {
+		t.Errorf("before any Update FrameStats = %s, %g, want nothing", took, rate)
+	}
+	for i := 0; i < 2; i++ {
+		if err := s.Update(); err != nil {
+			t.Fatalf("Update: %v", err)
+		}
+	}
+	if took, rate := s.FrameStats(); took < time.Millisecond || rate <= 0 {
+		t.Errorf("after two Updates FrameStats = %s, %g, want at least a millisecond a frame and a rate", took, rate)
+	}
+}
+
+func TestValidateLength(t *testing.T) {
+	for _, length := range []int{0, -1} {
+		c := testConfig(length)
+		var configErr *ConfigError
+		if err := c.Validate(); !errors.As(err, &configErr) {
+			t.Errorf("Validate with length %d = %v, want a ConfigError", length, err)
+		}
+	}
+	if err := testConfig(1).Validate(); err != nil {
+		t.Errorf("Validate with length 1 = %v", err)
+	}
+}
+
+func TestNewHertz(t *testing.T) {
+	c := testConfig(3)
+	c.Hertz = 0
+	s, err := New(nil, c, io.Discard)
+	var configErr *ConfigError
+	if s != nil || !errors.As(err, &configErr) {
+		t.Errorf("New with hertz 0 = %v, %v, want a ConfigError", s, err)
+	}
+}
+
+func quietLogger() *limlog.Limlog {
+	return limlog.NewLimlogZapWithConfig(limlog.NewZapConfigWithLevel(zap.ErrorLevel))
+}
+
+func TestInitDryRun(t *testing.T) {
+	s, err := Init(quietLogger(), testConfig(3), true)
+	if err != nil {
+		t.Fatalf("Init: %v", err)
+	}
+	if _, ok := s.Display.(*DryRun); !ok {
+		t.Errorf("dry run display is a %T, want a *DryRun", s.Display)
+	}
+	if s.spidev != nil {
+		t.Error("dry run opened the SPI device")
+	}
+}
+
+func TestDryRunFrames(t *testing.T) {
+	s, err := Init(quietLogger(), testConfig(3), true)
+	if err != nil {
+		t.Fatalf("Init: %v", err)
+	}
+	for i := 0; i < 3; i++ {
+		if err := s.Update(); err != nil {
+			t.Fatalf("Update: %v", err)
+		}
+	}
+	if got := s.Display.(*DryRun).Frames; got != 4 {
+		t.Errorf("DryRun counted %d frames, want 4: the first from New and 3 Updates", got)
+	}
+}
+
+// recorder keeps every frame written to it.
+type recorder struct {
+	sync.Mutex
+	frames [][]byte
+}
+
+func (r *recorder) Write(buf []byte) (int, error) {
+	r.Lock()
+	defer r.Unlock()
+	r.frames = append(r.frames, append([]byte(nil), buf...))
+	return len(buf), nil
+}
+
+// since returns the frames written after the first n.
+func (r *recorder) since(n int) [][]byte {
+	r.Lock()
+	defer r.Unlock()
+	return append([][]byte(nil), r.frames[n:]...)
+}
+
+func (r *recorder) count() int {
+	r.Lock()
+	defer r.Unlock()
+	return len(r.frames)
+}
+
+// litStrip is a strip drawing on a recorder with one pixel lit red.
+func litStrip(t *testing.T, c *Config) (*Strip, *recorder) {
+	t.Helper()
+	display := &recorder{}
+	s := newTestStrip(t, c, display)
+	p, err := s.Add("a.service")
+	if err != nil {
+		t.Fatalf("Add: %v", err)
+	}
+	p.ApplyState("failed", led.Look{Colour: "ff0000"})
+	if err := s.Update(); err != nil {
+		t.Fatalf("Update: %v", err)
+	}
+	return s, display
+}
+
+func TestClose(t *testing.T) {
+	s, display := litStrip(t, testConfig(3))
+	before := display.count()
+	if err := s.Close(); err != nil {
+		t.Fatalf("Close: %v", err)
+	}
+	if err := s.Close(); err != nil {
+		t.Fatalf("second Close: %v", err)
+	}
+	frames := display.since(before)
+	if len(frames) != 1 || !bytes.Equal(frames[0], make([]byte, 9)) {
+		t.Errorf("Close wrote %x, want one all off frame", frames)
+	}
+	if err := s.Update(); !errors.Is(err, ErrClosed) {
+		t.Errorf("Update after Close = %v, want ErrClosed", err)
+	}
+}
+
+func TestAllOff(t *testing.T) {
+	s, display := litStrip(t, testConfig(3))
+	before := display.count()
+	if err := s.AllOff(); err != nil {
+		t.Fatalf("AllOff: %v", err)
+	}
+	frames := display.since(before)
+	if len(frames) != 1 || !bytes.Equal(frames[0], make([]byte, 9)) {
+		t.Errorf("AllOff wrote %x, want exactly one zero frame", frames)
+	}
+	if got := s.Led("a.service").CurrentStatus(); got != "failed" {
+		t.Errorf("status after AllOff = %q, want failed kept", got)
+	}
+}
+
+func TestRunCancel(t *testing.T) {
+	c := testConfig(3)
+	c.Hertz = 100
+	s, display := litStrip(t, c)
+	ctx, cancel := context.WithCancel(context.Background())
+	done := make(chan error)
+	go func() { done <- s.Run(ctx) }()
+	time.Sleep(50 * time.Millisecond)
+	cancel()
+	select {
+	case err := <-done:
+		if err != nil {
+			t.Fatalf("Run: %v", err)
+		}
+	case <-time.After(time.Second):
+		t.Fatal("Run still going a second after ctx was cancelled")
+	}
+	frames := display.since(0)
+	if last := frames[len(frames)-1]; !bytes.Equal(last, make([]byte, 9)) {
+		t.Errorf("last frame = %x, want all off", last)
+	}
+	time.Sleep(30 * time.Millisecond)
+	if n := display.count(); n != len(frames) {
+		t.Errorf("%d frames written after Run returned", n-len(frames))
+	}
+}
+
+func TestCloseKeepOnExit(t *testing.T) {
+	c := testConfig(3)
+	c.KeepOnExit = true
+	s, display := litStrip(t, c)
+	before := display.count()
+	if err := s.Close(); err != nil {
+		t.Fatalf("Close: %v", err)
+	}
+	if frames := display.since(before); len(frames) != 0 {
+		t.Errorf("Close with keep_on_exit wrote %x, want no off frame", frames)
+	}
+}
+
+func TestSetFrequency(t *testing.T) {
+	s := newTestStrip(t, testConfig(3), io.Discard)
+	if err := s.SetFrequency(50); err != nil {
+		t.Fatalf("SetFrequency(50): %v", err)
+	}
+	if s.Config.Hertz != 50 {
+		t.Errorf("hertz = %d, want 50", s.Config.Hertz)
+	}
+	var configErr *ConfigError
+	if err := s.SetFrequency(0); !errors.As(err, &configErr) {
+		t.Errorf("SetFrequency(0) = %v, want a ConfigError", err)
+	}
+	if s.Config.Hertz != 50 {
+		t.Errorf("hertz = %d after a rejected change, want 50 kept", s.Config.Hertz)
+	}
+}
+
+func TestNeedsUpdate(t *testing.T) {
+	s, _ := litStrip(t, testConfig(3))
+	if s.NeedsUpdate() {
+		t.Error("NeedsUpdate = true straight after Update")
+	}
+	if err := s.SetColourByUnit("a.service", "00ff00"); err != nil {
+		t.Fatalf("SetColourByUnit: %v", err)
+	}
+	if !s.NeedsUpdate() {
+		t.Error("NeedsUpdate = false after a colour was set")
+	}
+	if err := s.Update(); err != nil {
+		t.Fatalf("Update: %v", err)
+	}
+	if s.NeedsUpdate() {
+		t.Error("NeedsUpdate = true after Update drew the new colour")
+	}
+}
+
+func TestResizeKeepsColours(t *testing.T) {
+	s := newTestStrip(t, testConfig(3), io.Discard)
+	codes := []string{"ff0000", "00ff00", "0000ff", "ffff00", "00ffff"}
+	add := func(n int) {
+		for i := len(s.Leds()); i < n; i++ {
+			p, err := s.Add(fmt.Sprintf("unit-%d.service", i))
+			if err != nil {
+				t.Fatalf("Add: %v", err)
+			}
+			p.ApplyState("active", led.Look{Colour: codes[i]})
+		}
+	}
+	check := func(length int) {
+		t.Helper()
+		if got := len(s.Buffer()); got != length*3 {
+			t.Errorf("frame is %d bytes after resizing to %d, want %d", got, length, length*3)
+		}
+		leds := s.Leds()
+		if len(leds) > length {
+			t.Errorf("%d pixels on a strip of %d", len(leds), length)
+		}
+		for i, p := range leds {
+			if p.CurrentColour() != codes[i] {
+				t.Errorf("pixel %d is %s after resizing to %d, want %s kept", i, p.CurrentColour(), length, codes[i])
+			}
+		}
+	}
+
+	add(3)
+	if err := s.Resize(5); err != nil {
+		t.Fatalf("Resize(5): %v", err)
+	}
+	check(5)
+	add(5)
+	if err := s.Resize(2); err != nil {
+		t.Fatalf("Resize(2): %v", err)
+	}
+	check(2)
+	if n := len(s.Leds()); n != 2 {
+		t.Errorf("%d pixels after shrinking to 2, want 2", n)
+	}
+}
+
+func TestSetColourByUnitUnknown(t *testing.T) {
+	s, _ := litStrip(t, testConfig(3))
+	if err := s.SetColourByUnit("nope.service", "00ff00"); !errors.Is(err, ErrUnknownUnit) {
+		t.Errorf("SetColourByUnit(nope.service) = %v, want ErrUnknownUnit", err)
+	}
+	if err := s.SetColourByUnit(" A.Service ", "00ff00"); err != nil {
+		t.Errorf("SetColourByUnit with other case and spacing = %v, want the pixel found", err)
+	}
+}
+
+func TestForce(t *testing.T) {
+	s, _ := litStrip(t, testConfig(2))
+	s.Force("0000ff")
+	s.Override("00ff00")
+	want := []byte{0, 0, 0xff, 0, 0, 0xff}
+	if got := s.Buffer(); !bytes.Equal(got, want) {
+		t.Errorf("forced frame = %x, want %x over the state colour and override", got, want)
+	}
+}
@@ -0,0 +1,61 @@
+package main
+
+import (
+	"flag"
+	"fmt"
+	"io"
+	"os"
+	"sort"
+
+	systemd "github.com/coreos/go-systemd/v22/dbus" // change namespace
+)
+
+// listUnits prints the units systemd has loaded with their states, to help
+// write a config. An optional glob in args narrows them down, and -user lists
+// the user's units rather than the system's.
+func listUnits(args []string) error {
+	flags := flag.NewFlagSet("list-units", flag.ExitOnError)
+	user := flags.Bool("user", false, "list units on the user bus")
+	_ = flags.Parse(args)
+
+	var conn *systemd.Conn
+	var err error
+	if *user {
+		conn, err = systemd.NewUserConnection()
+	} else {
+		conn, err = connect(*dbusAddress)
+	}
+	if err != nil {
+		return err
+	}
+	defer conn.Close()
+
+	units, err := conn.ListUnits()
+	if err != nil {
+		return err
+	}
+	pattern := "*"
+	if flags.NArg() > 0 {
+		pattern = flags.Arg(0)
+	}
+	return formatUnits(os.Stdout, units, pattern)
+}
+
+// formatUnits writes a line per unit matching pattern, sorted by name, with
+// its active and sub state as a states_map key would name them.
+func formatUnits(w io.Writer, units []systemd.UnitStatus, pattern string) error {
+	byName := make(map[string]systemd.UnitStatus, len(units))
+	names := make([]string, 0, len(units))
+	for _, unit := range units {
+		byName[unit.Name] = unit
+		names = append(names, unit.Name)
+	}
+	sort.Strings(names)
+	for _, name := range matchUnits(pattern, names) {
+		unit := byName[name]
+		if _, err := fmt.Fprintf(w, "%-50s %s/%s\n", name, unit.ActiveState, unit.SubState); err != nil {
+			return err
+		}
+	}
+	return nil
+}
@@ -0,0 +1,39 @@
+package main
+
+import (
+	"bytes"
+	"fmt"
+	"testing"
+
+	systemd "github.com/coreos/go-systemd/v22/dbus" // change namespace
+)
+
+func TestFormatUnits(t *testing.T) {
+	units := []systemd.UnitStatus{
+		{Name: "sshd.service", ActiveState: "active", SubState: "running"},
+		{Name: "docker.socket", ActiveState: "active", SubState: "listening"},
+		{Name: "backup.timer", ActiveState: "active", SubState: "waiting"},
+		{Name: "backup.service", ActiveState: "failed", SubState: "failed"},
+	}
+	line := func(name, state string) string {
+		return fmt.Sprintf("%-50s %s\n", name, state)
+	}
+	tests := []struct {
+		pattern string
+		want    string
+	}{
+		{"*", line("backup.service", "failed/failed") + line("backup.timer", "active/waiting") + line("docker.socket", "active/listening") + line("sshd.service", "active/running")},
+		{"backup.*", line("backup.service", "failed/failed") + line("backup.timer", "active/waiting")},
+		{"*.service", line("backup.service", "failed/failed") + line("sshd.service", "active/running")},
+		{"nginx.service", ""},
+	}
+	for _, tt := range tests {
+		var out bytes.Buffer
+		if err := formatUnits(&out, units, tt.pattern); err != nil {
+			t.Fatalf("formatUnits(%q): %v", tt.pattern, err)
+		}
+		if got := out.String(); got != tt.want {
+			t.Errorf("formatUnits(%q) =\n%s\nwant\n%s", tt.pattern, got, tt.want)
+		}
+	}
+}
@@ -0,0 +1,45 @@
+package main
+
+import (
+	"sync/atomic"
+	"time"
+
+	"github.com/shift/systemd-status-leds/strip"
+
+	"go.uber.org/zap"
+)
+
+// lastSeen is when systemd last answered, by event or query, in Unix nanoseconds.
+var lastSeen atomic.Int64
+
+// alive records that systemd has just answered.
+func alive() {
+	lastSeen.Store(time.Now().UnixNano())
+}
+
+// stale reports whether nothing has been heard since last for longer than
+// timeout. A zero timeout never goes stale.
+func stale(last time.Time, now time.Time, timeout time.Duration) bool {
+	return timeout > 0 && now.Sub(last) > timeout
+}
+
+// watchdog logs when systemd goes quiet for longer than the configured
+// timeout, covering the strip in the watchdog colour until it answers again.
+func watchdog(s *strip.Strip) {
+	wasStale := false
+	for {
+		time.Sleep(time.Second)
+		isStale := stale(time.Unix(0, lastSeen.Load()), time.Now(), C.Watchdog.Timeout)
+		if isStale && !wasStale {
+			logr.Error("Nothing heard from systemd", zap.Duration("timeout", C.Watchdog.Timeout))
+			if C.Watchdog.Colour != "" {
+				s.Override(C.Watchdog.Colour)
+			}
+		}
+		if !isStale && wasStale {
+			logr.Info("systemd is answering again")
+			s.Override("")
+		}
+		wasStale = isStale
+	}
+}
@@ -0,0 +1,28 @@
+package main
+
+import (
+	"testing"
+	"time"
+)
+
+func TestStale(t *testing.T) {
+	last := time.Now()
+	tests := []struct {
+		name    string
+		since   time.Duration
+		timeout time.Duration
+		want    bool
+	}{
+		{name: "just heard", since: 0, timeout: time.Minute, want: false},
+		{name: "within timeout", since: 30 * time.Second, timeout: time.Minute, want: false},
+		{name: "at timeout", since: time.Minute, timeout: time.Minute, want: false},
+		{name: "past timeout", since: time.Minute + time.Millisecond, timeout: time.Minute, want: true},
+		{name: "disabled", since: time.Hour, timeout: 0, want: false},
+		{name: "clock went back", since: -time.Hour, timeout: time.Minute, want: false},
+	}
+	for _, tt := range tests {
+		if got := stale(last, last.Add(tt.since), tt.timeout); got != tt.want {
+			t.Errorf("%s: stale(%s after, %s) = %t, want %t", tt.name, tt.since, tt.timeout, got, tt.want)
+		}
+	}
+}